@@ -1,7 +1,7 @@
 use crate::ray::Ray;
 use crate::vec3::Vec3;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Aabb {
     pub min: Vec3,
     pub max: Vec3,
@@ -24,6 +24,13 @@ impl Aabb {
         self.min = self.min.min_vec(p);
         self.max = self.max.max_vec(p);
     }
+    pub fn surface_area(&self) -> f32 {
+        let extent = self.max - self.min;
+        if extent.x < 0.0 || extent.y < 0.0 || extent.z < 0.0 {
+            return 0.0;
+        }
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
     pub fn intersect(&self, ray: &Ray) -> f32 {
         let t1 = (self.min.x - ray.origin.x) * ray.inv_direction.x;
         let t2 = (self.max.x - ray.origin.x) * ray.inv_direction.x;
@@ -40,7 +47,7 @@ impl Aabb {
         let tmin = tmin.max(t1.min(t2));
         let tmax = tmax.min(t1.max(t2));
 
-        if tmax >= tmin.max(0.0) {
+        if tmax >= tmin.max(ray.t_min) && tmin <= ray.t_max {
             tmin
         } else {
             f32::MAX
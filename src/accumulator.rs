@@ -0,0 +1,45 @@
+use crate::camera::Camera;
+use crate::vec3::Vec3;
+use rayon::prelude::*;
+
+pub struct Accumulator {
+    pub sum: Vec<Vec3>,
+    pub samples: u32,
+    pose: Option<(Vec3, f32, f32)>,
+}
+
+impl Accumulator {
+    pub fn new(len: usize) -> Self {
+        Self {
+            sum: vec![Vec3::new(0.0, 0.0, 0.0); len],
+            samples: 0,
+            pose: None,
+        }
+    }
+    pub fn reset(&mut self) {
+        self.sum
+            .par_iter_mut()
+            .for_each(|c| *c = Vec3::new(0.0, 0.0, 0.0));
+        self.samples = 0;
+    }
+    pub fn track(&mut self, camera: &Camera) {
+        let pose = Some((camera.pos, camera.yaw, camera.pitch));
+        if self.pose != pose {
+            self.pose = pose;
+            self.reset();
+        }
+    }
+    pub fn add(&mut self, frame: &[Vec3]) {
+        self.sum
+            .par_iter_mut()
+            .zip(frame.par_iter())
+            .for_each(|(sum, color)| *sum = *sum + *color);
+        self.samples += 1;
+    }
+    pub fn resolve(&self, out: &mut [Vec3]) {
+        let scale = 1.0 / self.samples.max(1) as f32;
+        out.par_iter_mut()
+            .zip(self.sum.par_iter())
+            .for_each(|(color, sum)| *color = *sum * scale);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::bvh;
+use crate::constants::RAY_EPSILON;
+use crate::objects::Intersection;
+use crate::ray::Ray;
+use crate::rng::Rng;
+use crate::scene::Scene;
+use crate::surface::Surface;
+use crate::tonemap::{ToneMapper, ToneMapping};
+use crate::vec3::Vec3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aov {
+    Normal,
+    Depth,
+    Albedo,
+    ObjectId,
+    Barycentrics,
+    ShadowMask,
+    BvhHeatmap,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 7] = [
+        Aov::Normal,
+        Aov::Depth,
+        Aov::Albedo,
+        Aov::ObjectId,
+        Aov::Barycentrics,
+        Aov::ShadowMask,
+        Aov::BvhHeatmap,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Normal => "normal",
+            Aov::Depth => "depth",
+            Aov::Albedo => "albedo",
+            Aov::ObjectId => "object-id",
+            Aov::Barycentrics => "barycentrics",
+            Aov::ShadowMask => "shadow",
+            Aov::BvhHeatmap => "bvh-heatmap",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|aov| aov.name() == name)
+    }
+
+    pub fn next(self) -> Option<Self> {
+        let i = Self::ALL.iter().position(|&a| a == self)?;
+        Self::ALL.get(i + 1).copied()
+    }
+
+    pub fn is_data(self) -> bool {
+        matches!(
+            self,
+            Aov::Normal | Aov::Depth | Aov::Barycentrics | Aov::ShadowMask
+        )
+    }
+
+    pub fn tone_mapping(self) -> ToneMapping {
+        ToneMapping {
+            operator: ToneMapper::Clamp,
+            srgb: !self.is_data(),
+            ..ToneMapping::default()
+        }
+    }
+
+    pub fn evaluate(self, ray: &Ray, forward: Vec3, scene: &Scene, rng: &mut Rng) -> Vec3 {
+        bvh::take_node_visits();
+        let hit = scene.bvh.intersect(ray, &scene.objects);
+        let visits = bvh::take_node_visits() as f32;
+        let Some(hit) = hit else {
+            return match self {
+                Aov::BvhHeatmap => Vec3::new(visits, visits, visits),
+                _ => Vec3::new(0.0, 0.0, 0.0),
+            };
+        };
+        match self {
+            Aov::Normal => hit.normal * 0.5 + Vec3::new(0.5, 0.5, 0.5),
+            Aov::Depth => {
+                let depth = (hit.point - ray.origin).dot(forward);
+                Vec3::new(depth, depth, depth)
+            }
+            Aov::Albedo => Surface::new(ray, &hit, &scene.textures).albedo,
+            Aov::ObjectId => id_color(hit.object),
+            Aov::Barycentrics => hit.barycentric,
+            Aov::ShadowMask => {
+                let lit = shadow_mask(&hit, scene, rng);
+                Vec3::new(lit, lit, lit)
+            }
+            Aov::BvhHeatmap => Vec3::new(visits, visits, visits),
+        }
+    }
+
+    pub fn normalize(self, buffer: &mut [Vec3]) {
+        let max = buffer.iter().map(|c| c.x).fold(0.0, f32::max);
+        if max <= 0.0 {
+            return;
+        }
+        match self {
+            Aov::Depth => {
+                let min = buffer
+                    .iter()
+                    .map(|c| c.x)
+                    .filter(|&d| d > 0.0)
+                    .fold(max, f32::min);
+                let range = (max - min).max(1e-6);
+                for c in buffer.iter_mut().filter(|c| c.x > 0.0) {
+                    let shade = 1.0 - (c.x - min) / range;
+                    *c = Vec3::new(shade, shade, shade);
+                }
+            }
+            Aov::BvhHeatmap => {
+                for c in buffer.iter_mut() {
+                    *c = heat(c.x / max);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn id_color([outer, inner]: [u32; 2]) -> Vec3 {
+    let hash = (outer.wrapping_add(1).wrapping_mul(0x9E37_79B1)
+        ^ inner.wrapping_add(1).wrapping_mul(0x85EB_CA77))
+    .wrapping_mul(0xC2B2_AE3D);
+    let channel = |shift: u32| ((hash >> shift) & 0xFF) as f32 / 255.0;
+    Vec3::new(channel(0), channel(8), channel(16))
+}
+
+fn heat(t: f32) -> Vec3 {
+    let t = t.clamp(0.0, 1.0);
+    if t < 0.5 {
+        Vec3::new(0.0, 2.0 * t, 1.0 - 2.0 * t)
+    } else {
+        Vec3::new(2.0 * t - 1.0, 2.0 - 2.0 * t, 0.0)
+    }
+}
+
+fn shadow_mask(hit: &Intersection, scene: &Scene, rng: &mut Rng) -> f32 {
+    let mut lit = 0;
+    let mut sampled = 0;
+    for light in &scene.lights {
+        let Some(sample) = light.sample(hit.point, rng) else {
+            continue;
+        };
+        sampled += 1;
+        if hit.normal.dot(sample.direction) <= 0.0 {
+            continue;
+        }
+        let shadow_ray = Ray::with_interval(
+            hit.point,
+            sample.direction,
+            RAY_EPSILON,
+            sample.distance - RAY_EPSILON,
+        );
+        if !scene.bvh.occluded(&shadow_ray, &scene.objects) {
+            lit += 1;
+        }
+    }
+    if sampled == 0 {
+        return 1.0;
+    }
+    lit as f32 / sampled as f32
+}
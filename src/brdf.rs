@@ -0,0 +1,146 @@
+use crate::rng::Rng;
+use crate::vec3::Vec3;
+use std::f32::consts::PI;
+
+const DIELECTRIC_F0: f32 = 0.04;
+const MIN_ALPHA: f32 = 1e-3;
+
+pub fn alpha(roughness: f32) -> f32 {
+    (roughness * roughness).max(MIN_ALPHA)
+}
+
+pub fn base_reflectance(albedo: Vec3, metallic: f32) -> Vec3 {
+    Vec3::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0) * (1.0 - metallic) + albedo * metallic
+}
+
+pub fn fresnel_schlick(f0: Vec3, cos_theta: f32) -> Vec3 {
+    let weight = (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5);
+    f0 * (1.0 - weight) + Vec3::new(1.0, 1.0, 1.0) * weight
+}
+
+pub fn conductor_fresnel(cos_theta: f32, eta: Vec3, k: Vec3) -> Vec3 {
+    let channel = |n: f32, k: f32| {
+        let cos2 = cos_theta.clamp(0.0, 1.0).powi(2);
+        let sin2 = 1.0 - cos2;
+        let t0 = n * n - k * k - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * n * n * k * k).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cos_theta.clamp(0.0, 1.0) * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        0.5 * (rp + rs)
+    };
+    Vec3::new(
+        channel(eta.x, k.x),
+        channel(eta.y, k.y),
+        channel(eta.z, k.z),
+    )
+}
+
+pub fn ggx_distribution(n_dot_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+fn smith_g1(n_dot_x: f32, alpha: f32) -> f32 {
+    let k = alpha * 0.5;
+    n_dot_x / (n_dot_x * (1.0 - k) + k)
+}
+
+pub fn smith_geometry(n_dot_v: f32, n_dot_l: f32, alpha: f32) -> f32 {
+    smith_g1(n_dot_v, alpha) * smith_g1(n_dot_l, alpha)
+}
+
+pub fn cook_torrance(
+    normal: Vec3,
+    view: Vec3,
+    light: Vec3,
+    fresnel: impl Fn(f32) -> Vec3,
+    diffuse: Vec3,
+    roughness: f32,
+) -> (Vec3, Vec3) {
+    let n_dot_l = normal.dot(light);
+    let n_dot_v = normal.dot(view);
+    if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+        return (Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0));
+    }
+    let alpha = alpha(roughness);
+    let halfway = (light + view).normalize();
+    let n_dot_h = normal.dot(halfway).max(0.0);
+    let fresnel = fresnel(view.dot(halfway));
+    let specular = fresnel
+        * (ggx_distribution(n_dot_h, alpha) * smith_geometry(n_dot_v, n_dot_l, alpha)
+            / (4.0 * n_dot_v * n_dot_l));
+    let k_diffuse = Vec3::new(1.0, 1.0, 1.0) - fresnel;
+    (
+        diffuse.mul_vec(k_diffuse) * (n_dot_l / PI),
+        specular * n_dot_l,
+    )
+}
+
+pub fn sample_cosine_hemisphere(normal: Vec3, rng: &mut Rng) -> Vec3 {
+    let r = rng.next_f32().sqrt();
+    let phi = 2.0 * PI * rng.next_f32();
+    let (tangent, bitangent) = normal.orthonormal_basis();
+    (tangent * (r * phi.cos())
+        + bitangent * (r * phi.sin())
+        + normal * (1.0 - r * r).max(0.0).sqrt())
+    .normalize()
+}
+
+pub fn sample_ggx_reflection(normal: Vec3, view: Vec3, roughness: f32, rng: &mut Rng) -> Vec3 {
+    let alpha = alpha(roughness);
+    let (u1, u2) = (rng.next_f32(), rng.next_f32());
+    let cos_theta = ((1.0 - u1) / (1.0 + (alpha * alpha - 1.0) * u1)).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u2;
+    let (tangent, bitangent) = normal.orthonormal_basis();
+    let halfway = (tangent * (sin_theta * phi.cos())
+        + bitangent * (sin_theta * phi.sin())
+        + normal * cos_theta)
+        .normalize();
+    let reflected = (-view).reflect(halfway).normalize();
+    if reflected.dot(normal) > 0.0 {
+        reflected
+    } else {
+        (-view).reflect(normal).normalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::CONDUCTORS;
+
+    #[test]
+    fn conductor_fresnel_matches_normal_incidence_reflectance() {
+        for &(name, eta, k) in CONDUCTORS.iter() {
+            let expected =
+                |n: f32, k: f32| ((n - 1.0).powi(2) + k * k) / ((n + 1.0).powi(2) + k * k);
+            let f = conductor_fresnel(1.0, eta, k);
+            for (actual, expected) in [
+                (f.x, expected(eta.x, k.x)),
+                (f.y, expected(eta.y, k.y)),
+                (f.z, expected(eta.z, k.z)),
+            ] {
+                assert!(
+                    (actual - expected).abs() < 1e-5,
+                    "{}: {} != {}",
+                    name,
+                    actual,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn conductor_fresnel_is_total_at_grazing_angles() {
+        let f = conductor_fresnel(0.0, Vec3::new(0.2, 0.4, 1.4), Vec3::new(3.9, 2.4, 1.8));
+        assert!(f.x > 0.999 && f.y > 0.999 && f.z > 0.999);
+    }
+}
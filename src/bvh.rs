@@ -1,91 +1,279 @@
 use crate::aabb::Aabb;
-use crate::objects::{Intersection, Object};
+use crate::objects::{Hit, Intersection, Object};
 use crate::ray::Ray;
 use crate::vec3::Vec3;
+use rayon::prelude::*;
+use std::cell::Cell;
 use std::cmp::Ordering;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct BvhNode {
     pub aabb: Aabb,
     pub left_first: u32,
     pub count: u32,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PrimRef {
+    pub object: u32,
+    pub primitive: u32,
+}
+
 pub struct Bvh {
     pub nodes: Vec<BvhNode>,
-    pub prim_indices: Vec<usize>,
+    pub prim_indices: Vec<PrimRef>,
+    pub build_cost: f32,
+}
+
+thread_local! {
+    static NODE_VISITS: Cell<u32> = const { Cell::new(0) };
+}
+
+pub fn take_node_visits() -> u32 {
+    NODE_VISITS.replace(0)
 }
 
 pub struct BvhPrimitive {
-    index: usize,
+    index: PrimRef,
     aabb: Aabb,
     center: Vec3,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SplitMethod {
+    Median,
+    Sah,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BvhConfig {
+    pub method: SplitMethod,
+    pub bins: usize,
+    pub leaf_cost: f32,
+    pub traversal_cost: f32,
+    pub max_leaf_size: usize,
+    pub rebuild_threshold: f32,
+    pub parallel_threshold: usize,
+}
+
+impl Default for BvhConfig {
+    fn default() -> Self {
+        Self {
+            method: SplitMethod::Median,
+            bins: 16,
+            leaf_cost: 1.0,
+            traversal_cost: 1.0,
+            max_leaf_size: 2,
+            rebuild_threshold: 1.5,
+            parallel_threshold: 4096,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct SahBin {
+    aabb: Aabb,
+    count: usize,
+}
+
+enum BuildNode {
+    Leaf {
+        aabb: Aabb,
+        start: usize,
+        count: usize,
+    },
+    Interior {
+        aabb: Aabb,
+        children: Box<[BuildNode; 2]>,
+    },
+}
+
+const TRAVERSAL_STACK_SIZE: usize = 64;
+const MAX_DEPTH: usize = TRAVERSAL_STACK_SIZE - 1;
+
+fn bounds(primitives: &[BvhPrimitive], parallel_threshold: usize) -> (Aabb, Aabb) {
+    let fold = |(aabb, centroids): (Aabb, Aabb), prim: &BvhPrimitive| {
+        let mut centroids = centroids;
+        centroids.grow(prim.center);
+        (aabb.union(&prim.aabb), centroids)
+    };
+    let empty = || (Aabb::empty(), Aabb::empty());
+    if primitives.len() > parallel_threshold {
+        primitives
+            .par_iter()
+            .fold(empty, fold)
+            .reduce(empty, |a, b| (a.0.union(&b.0), a.1.union(&b.1)))
+    } else {
+        primitives.iter().fold(empty(), fold)
+    }
+}
+
 impl Bvh {
-    pub fn build(objects: &[Object]) -> Self {
+    pub fn build(objects: &[Object], config: &BvhConfig) -> Self {
         let mut primitives: Vec<BvhPrimitive> = objects
-            .iter()
+            .par_iter()
             .enumerate()
-            .map(|(i, obj)| {
-                let aabb = obj.aabb();
-                let center = (aabb.min + aabb.max) * 0.5;
-                BvhPrimitive {
-                    index: i,
-                    aabb,
-                    center,
-                }
+            .flat_map(|(i, obj)| {
+                (0..obj.primitive_count()).into_par_iter().map(move |p| {
+                    let aabb = obj.aabb(p);
+                    let center = (aabb.min + aabb.max) * 0.5;
+                    BvhPrimitive {
+                        index: PrimRef {
+                            object: i as u32,
+                            primitive: p as u32,
+                        },
+                        aabb,
+                        center,
+                    }
+                })
             })
             .collect();
 
-        let mut nodes = Vec::with_capacity(objects.len() * 2);
-        let mut prim_indices = vec![0; objects.len()];
+        let root = Self::split(&mut primitives, config, 0, 0);
 
-        let root_node = BvhNode {
+        let mut nodes = Vec::with_capacity(primitives.len() * 2);
+        nodes.push(BvhNode {
             aabb: Aabb::empty(),
             left_first: 0,
             count: 0,
-        };
-        nodes.push(root_node);
-
-        Self::split(
-            &mut nodes,
-            &mut prim_indices,
-            &mut primitives,
-            0,
-            0,
-            objects.len(),
-        );
+        });
+        Self::flatten(&root, &mut nodes, 0);
+
+        let prim_indices = primitives.iter().map(|p| p.index).collect();
 
-        Bvh {
+        let mut bvh = Bvh {
             nodes,
             prim_indices,
+            build_cost: 0.0,
+        };
+        bvh.build_cost = bvh.sah_cost(config);
+        bvh
+    }
+
+    pub fn refit(&mut self, objects: &[Object]) {
+        if self.nodes.len() == 1 {
+            let node = self.nodes[0];
+            self.nodes[0].aabb = self.leaf_bounds(&node, objects);
+            return;
         }
+        for i in (0..self.nodes.len()).rev() {
+            let node = self.nodes[i];
+            self.nodes[i].aabb = if node.count > 0 {
+                self.leaf_bounds(&node, objects)
+            } else {
+                let left = node.left_first as usize;
+                self.nodes[left].aabb.union(&self.nodes[left + 1].aabb)
+            };
+        }
+    }
+
+    fn leaf_bounds(&self, node: &BvhNode, objects: &[Object]) -> Aabb {
+        let start = node.left_first as usize;
+        self.prim_indices[start..start + node.count as usize]
+            .iter()
+            .fold(Aabb::empty(), |aabb, prim| {
+                aabb.union(&objects[prim.object as usize].aabb(prim.primitive as usize))
+            })
+    }
+
+    pub fn sah_cost(&self, config: &BvhConfig) -> f32 {
+        let root_area = self.nodes[0].aabb.surface_area();
+        if self.prim_indices.is_empty() || root_area <= 0.0 {
+            return 0.0;
+        }
+        let total: f32 = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let cost = if node.count > 0 {
+                    config.leaf_cost * node.count as f32
+                } else {
+                    config.traversal_cost
+                };
+                cost * node.aabb.surface_area()
+            })
+            .sum();
+        total / root_area
+    }
+
+    pub fn needs_rebuild(&self, config: &BvhConfig) -> bool {
+        self.sah_cost(config) > self.build_cost * config.rebuild_threshold
     }
 
     fn split(
-        nodes: &mut Vec<BvhNode>,
-        global_indices: &mut [usize],
         primitives: &mut [BvhPrimitive],
-        node_idx: usize,
+        config: &BvhConfig,
         start: usize,
-        count: usize,
-    ) {
-        let mut aabb = Aabb::empty();
-        for i in 0..count {
-            aabb = aabb.union(&primitives[start + i].aabb);
+        depth: usize,
+    ) -> BuildNode {
+        let count = primitives.len();
+        let (aabb, centroid_bounds) = bounds(primitives, config.parallel_threshold);
+
+        let split = match config.method {
+            _ if depth >= MAX_DEPTH => None,
+            SplitMethod::Median if count > config.max_leaf_size.max(1) => {
+                Some(Self::median_split(primitives, &aabb, config))
+            }
+            SplitMethod::Median => None,
+            SplitMethod::Sah => Self::sah_split(primitives, &aabb, &centroid_bounds, config),
+        };
+
+        let Some(mid) = split else {
+            return BuildNode::Leaf { aabb, start, count };
+        };
+
+        let (left, right) = primitives.split_at_mut(mid);
+        let (left, right) = if count > config.parallel_threshold {
+            rayon::join(
+                || Self::split(left, config, start, depth + 1),
+                || Self::split(right, config, start + mid, depth + 1),
+            )
+        } else {
+            (
+                Self::split(left, config, start, depth + 1),
+                Self::split(right, config, start + mid, depth + 1),
+            )
+        };
+
+        BuildNode::Interior {
+            aabb,
+            children: Box::new([left, right]),
         }
-        nodes[node_idx].aabb = aabb;
-        nodes[node_idx].count = count as u32;
-        nodes[node_idx].left_first = start as u32;
+    }
 
-        if count <= 2 {
-            for i in 0..count {
-                global_indices[start + i] = primitives[start + i].index;
+    fn flatten(node: &BuildNode, nodes: &mut Vec<BvhNode>, node_idx: usize) {
+        match node {
+            BuildNode::Leaf { aabb, start, count } => {
+                nodes[node_idx] = BvhNode {
+                    aabb: *aabb,
+                    left_first: *start as u32,
+                    count: *count as u32,
+                };
+            }
+            BuildNode::Interior { aabb, children } => {
+                let left_child_idx = nodes.len();
+                nodes[node_idx] = BvhNode {
+                    aabb: *aabb,
+                    left_first: left_child_idx as u32,
+                    count: 0,
+                };
+                nodes.push(BvhNode {
+                    aabb: Aabb::empty(),
+                    left_first: 0,
+                    count: 0,
+                });
+                nodes.push(BvhNode {
+                    aabb: Aabb::empty(),
+                    left_first: 0,
+                    count: 0,
+                });
+                Self::flatten(&children[0], nodes, left_child_idx);
+                Self::flatten(&children[1], nodes, left_child_idx + 1);
             }
-            return;
         }
+    }
 
+    fn median_split(slice: &mut [BvhPrimitive], aabb: &Aabb, config: &BvhConfig) -> usize {
         let extent = aabb.max - aabb.min;
         let axis = if extent.x > extent.y && extent.x > extent.z {
             0
@@ -95,69 +283,149 @@ impl Bvh {
             2
         };
 
-        let slice = &mut primitives[start..start + count];
-        slice.sort_by(|a, b| {
-            let val_a = if axis == 0 {
-                a.center.x
-            } else if axis == 1 {
-                a.center.y
-            } else {
-                a.center.z
+        let compare = |a: &BvhPrimitive, b: &BvhPrimitive| {
+            a.center
+                .axis(axis)
+                .partial_cmp(&b.center.axis(axis))
+                .unwrap_or(Ordering::Equal)
+        };
+        if slice.len() > config.parallel_threshold {
+            slice.par_sort_by(compare);
+        } else {
+            slice.sort_by(compare);
+        }
+        slice.len() / 2
+    }
+
+    fn sah_split(
+        slice: &mut [BvhPrimitive],
+        aabb: &Aabb,
+        centroid_bounds: &Aabb,
+        config: &BvhConfig,
+    ) -> Option<usize> {
+        let count = slice.len();
+        let bin_count = config.bins.max(2);
+        let leaf_cost = config.leaf_cost * count as f32;
+        let parent_area = aabb.surface_area();
+
+        let mut best: Option<(usize, usize, f32)> = None;
+        for axis in 0..3 {
+            let min = centroid_bounds.min.axis(axis);
+            let extent = centroid_bounds.max.axis(axis) - min;
+            if extent <= 0.0 {
+                continue;
+            }
+            let scale = bin_count as f32 / extent;
+
+            let empty = || {
+                vec![
+                    SahBin {
+                        aabb: Aabb::empty(),
+                        count: 0,
+                    };
+                    bin_count
+                ]
+            };
+            let fold = |mut bins: Vec<SahBin>, prim: &BvhPrimitive| {
+                let b = (((prim.center.axis(axis) - min) * scale) as usize).min(bin_count - 1);
+                bins[b].aabb = bins[b].aabb.union(&prim.aabb);
+                bins[b].count += 1;
+                bins
             };
-            let val_b = if axis == 0 {
-                b.center.x
-            } else if axis == 1 {
-                b.center.y
+            let bins = if count > config.parallel_threshold {
+                slice
+                    .par_iter()
+                    .fold(empty, fold)
+                    .reduce(empty, |mut a, b| {
+                        for (a, b) in a.iter_mut().zip(&b) {
+                            a.aabb = a.aabb.union(&b.aabb);
+                            a.count += b.count;
+                        }
+                        a
+                    })
             } else {
-                b.center.z
+                slice.iter().fold(empty(), fold)
             };
-            val_a.partial_cmp(&val_b).unwrap_or(Ordering::Equal)
-        });
 
-        let mid = count / 2;
-        let left_child_idx = nodes.len();
-        let right_child_idx = left_child_idx + 1;
+            let mut right_area = vec![0.0; bin_count];
+            let mut right_count = vec![0; bin_count];
+            let mut acc = Aabb::empty();
+            let mut acc_count = 0;
+            for b in (1..bin_count).rev() {
+                acc = acc.union(&bins[b].aabb);
+                acc_count += bins[b].count;
+                right_area[b] = acc.surface_area();
+                right_count[b] = acc_count;
+            }
 
-        nodes[node_idx].left_first = left_child_idx as u32;
-        nodes[node_idx].count = 0;
+            let mut acc = Aabb::empty();
+            let mut acc_count = 0;
+            for b in 1..bin_count {
+                acc = acc.union(&bins[b - 1].aabb);
+                acc_count += bins[b - 1].count;
+                if acc_count == 0 || right_count[b] == 0 {
+                    continue;
+                }
+                let cost = config.traversal_cost
+                    + config.leaf_cost
+                        * (acc_count as f32 * acc.surface_area()
+                            + right_count[b] as f32 * right_area[b])
+                        / parent_area.max(f32::EPSILON);
+                if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                    best = Some((axis, b, cost));
+                }
+            }
+        }
 
-        nodes.push(BvhNode {
-            aabb: Aabb::empty(),
-            left_first: 0,
-            count: 0,
-        });
-        nodes.push(BvhNode {
-            aabb: Aabb::empty(),
-            left_first: 0,
-            count: 0,
-        });
+        let Some((axis, split_bin, cost)) = best else {
+            if count > config.max_leaf_size.max(1) {
+                return Some(Self::median_split(slice, aabb, config));
+            }
+            return None;
+        };
+        if cost >= leaf_cost && count <= config.max_leaf_size.max(1) {
+            return None;
+        }
 
-        Self::split(
-            nodes,
-            global_indices,
-            primitives,
-            left_child_idx,
-            start,
-            mid,
-        );
-        Self::split(
-            nodes,
-            global_indices,
-            primitives,
-            right_child_idx,
-            start + mid,
-            count - mid,
-        );
+        let min = centroid_bounds.min.axis(axis);
+        let scale = bin_count as f32 / (centroid_bounds.max.axis(axis) - min);
+        let mut mid = 0;
+        for i in 0..count {
+            let b = (((slice[i].center.axis(axis) - min) * scale) as usize).min(bin_count - 1);
+            if b < split_bin {
+                slice.swap(i, mid);
+                mid += 1;
+            }
+        }
+        Some(mid)
     }
 
     pub fn intersect(&self, ray: &Ray, objects: &[Object]) -> Option<Intersection> {
-        let mut closest_t = f32::MAX;
-        let mut closest_hit: Option<Intersection> = None;
-        let mut stack = [0_usize; 64];
+        let hit = self.closest_hit(ray, objects)?;
+        let front_face = hit.geometric_normal.dot(ray.direction) < 0.0;
+        Some(Intersection {
+            point: ray.origin + hit.t * ray.direction,
+            normal: if front_face { hit.normal } else { -hit.normal },
+            front_face,
+            uv: hit.uv,
+            barycentric: hit.barycentric,
+            object: hit.object,
+            material: hit.material,
+        })
+    }
+
+    pub fn closest_hit(&self, ray: &Ray, objects: &[Object]) -> Option<Hit> {
+        if self.prim_indices.is_empty() {
+            return None;
+        }
+        let mut closest_t = ray.t_max;
+        let mut closest_hit: Option<Hit> = None;
+        let mut stack = [0_usize; TRAVERSAL_STACK_SIZE];
         let mut stack_ptr = 0;
         stack[0] = 0;
 
-        while stack_ptr < 64 {
+        while stack_ptr < TRAVERSAL_STACK_SIZE {
+            NODE_VISITS.set(NODE_VISITS.get() + 1);
             let node_idx = stack[stack_ptr];
             let node = &self.nodes[node_idx];
             let dist_box = node.aabb.intersect(ray);
@@ -165,17 +433,16 @@ impl Bvh {
             if dist_box < closest_t {
                 if node.count > 0 {
                     for i in 0..node.count {
-                        let obj_idx = self.prim_indices[(node.left_first + i) as usize];
-                        let obj = &objects[obj_idx];
-                        if let Some((t, normal, mat)) = obj.intersect(ray) {
-                            if t < closest_t {
-                                closest_t = t;
-                                closest_hit = Some(Intersection {
-                                    point: ray.origin + t * ray.direction,
-                                    normal,
-                                    material: mat,
-                                });
-                            }
+                        let prim = self.prim_indices[(node.left_first + i) as usize];
+                        let obj = &objects[prim.object as usize];
+                        if let Some(hit) = obj.intersect(prim.primitive as usize, ray)
+                            && hit.t < closest_t
+                        {
+                            closest_t = hit.t;
+                            closest_hit = Some(Hit {
+                                object: [prim.object, hit.object[0]],
+                                ..hit
+                            });
                         }
                     }
                     if stack_ptr == 0 {
@@ -220,4 +487,205 @@ impl Bvh {
         }
         closest_hit
     }
+
+    pub fn occluded(&self, ray: &Ray, objects: &[Object]) -> bool {
+        if self.prim_indices.is_empty() {
+            return false;
+        }
+        let mut stack = [0_usize; TRAVERSAL_STACK_SIZE];
+        let mut stack_ptr = 1;
+
+        while stack_ptr > 0 {
+            stack_ptr -= 1;
+            let node = &self.nodes[stack[stack_ptr]];
+            let dist_box = node.aabb.intersect(ray);
+            if dist_box == f32::MAX {
+                continue;
+            }
+
+            if node.count > 0 {
+                for i in 0..node.count {
+                    let prim = self.prim_indices[(node.left_first + i) as usize];
+                    if objects[prim.object as usize].occludes(prim.primitive as usize, ray) {
+                        return true;
+                    }
+                }
+            } else {
+                stack[stack_ptr] = node.left_first as usize;
+                stack[stack_ptr + 1] = node.left_first as usize + 1;
+                stack_ptr += 2;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Material, Shading};
+    use crate::objects::{Sphere, Triangle};
+    use crate::rng::Rng;
+
+    fn material() -> Material {
+        Material {
+            albedo: Vec3::new(0.8, 0.8, 0.8),
+            emission: Vec3::new(0.0, 0.0, 0.0),
+            specular: 0.0,
+            shininess: 32.0,
+            checkered: false,
+            ior: 1.0,
+            transmission: 0.0,
+            albedo_texture: None,
+            shading: Shading::Phong,
+        }
+    }
+
+    fn random_point(rng: &mut Rng, extent: f32) -> Vec3 {
+        Vec3::new(
+            (rng.next_f32() * 2.0 - 1.0) * extent,
+            (rng.next_f32() * 2.0 - 1.0) * extent,
+            (rng.next_f32() * 2.0 - 1.0) * extent,
+        )
+    }
+
+    fn random_scene(rng: &mut Rng) -> Vec<Object> {
+        let mut objects = Vec::new();
+        for _ in 0..150 {
+            objects.push(Object::Sphere(Sphere {
+                center: random_point(rng, 10.0),
+                radius: 0.1 + rng.next_f32() * 0.6,
+                mat: material(),
+            }));
+        }
+        for _ in 0..150 {
+            let v0 = random_point(rng, 10.0);
+            objects.push(Object::Triangle(Triangle {
+                v0,
+                v1: v0 + random_point(rng, 1.0),
+                v2: v0 + random_point(rng, 1.0),
+                uvs: None,
+                normals: None,
+                mat: material(),
+            }));
+        }
+        objects
+    }
+
+    fn random_rays(rng: &mut Rng) -> Vec<Ray> {
+        (0..2000)
+            .map(|_| {
+                let origin = random_point(rng, 15.0);
+                let target = random_point(rng, 5.0);
+                Ray::new(origin, (target - origin).normalize())
+            })
+            .collect()
+    }
+
+    fn assert_same_hits(a: &Bvh, b: &Bvh, objects: &[Object], rays: &[Ray]) {
+        let mut hits = 0;
+        for ray in rays {
+            match (a.intersect(ray, objects), b.intersect(ray, objects)) {
+                (Some(x), Some(y)) => {
+                    hits += 1;
+                    assert_eq!(x.object, y.object);
+                    assert!((x.point - y.point).len() < 1e-4);
+                }
+                (None, None) => {}
+                _ => panic!("only one hierarchy reported a hit"),
+            }
+            assert_eq!(a.occluded(ray, objects), b.occluded(ray, objects));
+        }
+        assert!(
+            hits > rays.len() / 10,
+            "too few rays hit the scene: {}",
+            hits
+        );
+    }
+
+    #[test]
+    fn empty_hierarchy_reports_no_hits() {
+        let bvh = Bvh::build(&[], &BvhConfig::default());
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(bvh.intersect(&ray, &[]).is_none());
+        assert!(!bvh.occluded(&ray, &[]));
+    }
+
+    #[test]
+    fn sah_and_median_builds_find_the_same_closest_hits() {
+        let mut rng = Rng::new(7);
+        let objects = random_scene(&mut rng);
+        let rays = random_rays(&mut rng);
+        let median = Bvh::build(&objects, &BvhConfig::default());
+        let sah = Bvh::build(
+            &objects,
+            &BvhConfig {
+                method: SplitMethod::Sah,
+                ..BvhConfig::default()
+            },
+        );
+        assert_same_hits(&median, &sah, &objects, &rays);
+    }
+
+    #[test]
+    fn refit_matches_a_rebuild_after_objects_move() {
+        let mut rng = Rng::new(11);
+        let mut objects = random_scene(&mut rng);
+        let rays = random_rays(&mut rng);
+        let config = BvhConfig {
+            method: SplitMethod::Sah,
+            ..BvhConfig::default()
+        };
+        let mut refitted = Bvh::build(&objects, &config);
+        for object in &mut objects {
+            let offset = random_point(&mut rng, 3.0);
+            match object {
+                Object::Sphere(s) => s.center = s.center + offset,
+                Object::Triangle(t) => {
+                    t.v0 = t.v0 + offset;
+                    t.v1 = t.v1 + offset;
+                    t.v2 = t.v2 + offset;
+                }
+                _ => {}
+            }
+        }
+        refitted.refit(&objects);
+        let rebuilt = Bvh::build(&objects, &config);
+        assert_same_hits(&refitted, &rebuilt, &objects, &rays);
+    }
+
+    #[test]
+    fn parallel_builds_match_the_serial_build() {
+        let mut rng = Rng::new(13);
+        let objects: Vec<Object> = (0..5000)
+            .map(|_| {
+                let v0 = random_point(&mut rng, 20.0);
+                Object::Triangle(Triangle {
+                    v0,
+                    v1: v0 + random_point(&mut rng, 0.5),
+                    v2: v0 + random_point(&mut rng, 0.5),
+                    uvs: None,
+                    normals: None,
+                    mat: material(),
+                })
+            })
+            .collect();
+        for method in [SplitMethod::Median, SplitMethod::Sah] {
+            let config = BvhConfig {
+                method,
+                ..BvhConfig::default()
+            };
+            assert!(objects.len() > config.parallel_threshold);
+            let parallel = Bvh::build(&objects, &config);
+            let serial = Bvh::build(
+                &objects,
+                &BvhConfig {
+                    parallel_threshold: usize::MAX,
+                    ..config
+                },
+            );
+            assert_eq!(parallel.nodes, serial.nodes);
+            assert_eq!(parallel.prim_indices, serial.prim_indices);
+        }
+    }
 }
@@ -0,0 +1,289 @@
+use crate::aov::Aov;
+use crate::bvh::{BvhConfig, SplitMethod};
+use crate::camera::Camera;
+use crate::integrator::{self, Integrator};
+use crate::scene::RenderSettings;
+use crate::tonemap::ToneMapper;
+use crate::vec3::Vec3;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: raytracer [--headless] [options]
+
+Options:
+  --headless             render without opening a window
+  --scene <file>         load a TOML scene description
+  --environment <file>   equirectangular .hdr, .pfm, .png or .ppm background
+  --env-rotation <deg>   rotate the environment map around the vertical axis
+  --env-intensity <s>    environment radiance multiplier
+  --width <px>           output width (headless)
+  --height <px>          output height (headless)
+  --output <file>        .png, .ppm or .pfm output path (headless)
+  --aov <list|all>       also write comma-separated debug images: normal, depth,
+                         albedo, object-id, barycentrics, shadow, bvh-heatmap
+  --frames <n>           number of frames to render (headless)
+  --camera <x,y,z>       camera position
+  --yaw <deg>            camera yaw
+  --pitch <deg>          camera pitch
+  --yaw-step <deg>       yaw increment per frame (headless)
+  --max-bounces <n>      maximum Whitted ray depth
+  --integrator <name>    whitted or path
+  --shadow-samples <n>   shadow rays per area light
+  --spp <n>              accumulated samples per pixel (headless)
+  --adaptive <n>         maximum extra samples for edge pixels, 0 disables adaptive AA
+  --contrast <t>         perceived luminance step that marks an edge
+  --tonemap <op>         clamp, reinhard, reinhard-extended, aces or uncharted2
+  --exposure <ev>        exposure compensation in stops
+  --auto-exposure        scale exposure from the frame's average luminance
+  --white-point <l>      luminance mapped to white by reinhard-extended
+  --bvh <median|sah>     BVH split method
+  --sah-bins <n>         SAH bin count
+  --leaf-cost <c>        SAH primitive intersection cost
+  --traversal-cost <c>   SAH node traversal cost
+  --max-leaf-size <n>    maximum primitives per BVH leaf
+  --rebuild-threshold <r> SAH cost ratio after a refit that triggers a rebuild";
+
+pub struct Options {
+    pub headless: bool,
+    pub width: usize,
+    pub height: usize,
+    pub output: PathBuf,
+    pub aovs: Vec<Aov>,
+    pub frames: u32,
+    pub scene: Option<PathBuf>,
+    pub environment: Option<PathBuf>,
+    pub env_rotation: f32,
+    pub env_intensity: f32,
+    pub camera_pos: Option<Vec3>,
+    pub yaw: Option<f32>,
+    pub pitch: Option<f32>,
+    pub yaw_step: f32,
+    pub max_bounces: Option<u8>,
+    pub integrator: Option<&'static dyn Integrator>,
+    pub shadow_samples: Option<u32>,
+    pub samples_per_pixel: Option<u32>,
+    pub adaptive_samples: Option<u32>,
+    pub contrast_threshold: Option<f32>,
+    pub tone_mapper: Option<ToneMapper>,
+    pub exposure: Option<f32>,
+    pub auto_exposure: bool,
+    pub white_point: Option<f32>,
+    pub bvh: BvhConfig,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            headless: false,
+            width: 1920,
+            height: 1080,
+            output: PathBuf::from("render.png"),
+            aovs: Vec::new(),
+            frames: 1,
+            scene: None,
+            environment: None,
+            env_rotation: 0.0,
+            env_intensity: 1.0,
+            camera_pos: None,
+            yaw: None,
+            pitch: None,
+            yaw_step: 0.0,
+            max_bounces: None,
+            integrator: None,
+            shadow_samples: None,
+            samples_per_pixel: None,
+            adaptive_samples: None,
+            contrast_threshold: None,
+            tone_mapper: None,
+            exposure: None,
+            auto_exposure: false,
+            white_point: None,
+            bvh: BvhConfig::default(),
+        }
+    }
+}
+
+impl Options {
+    pub fn apply_overrides(&self, camera: &mut Camera, settings: &mut RenderSettings) {
+        if let Some(pos) = self.camera_pos {
+            camera.pos = pos;
+        }
+        if let Some(yaw) = self.yaw {
+            camera.yaw = yaw;
+        }
+        if let Some(pitch) = self.pitch {
+            camera.pitch = pitch;
+        }
+        if let Some(max_bounces) = self.max_bounces {
+            settings.max_bounces = max_bounces;
+        }
+        if let Some(integrator) = self.integrator {
+            settings.integrator = integrator;
+        }
+        if let Some(shadow_samples) = self.shadow_samples {
+            settings.shadow_samples = shadow_samples;
+        }
+        if let Some(samples_per_pixel) = self.samples_per_pixel {
+            settings.samples_per_pixel = samples_per_pixel;
+        }
+        if let Some(adaptive_samples) = self.adaptive_samples {
+            settings.adaptive_samples = adaptive_samples;
+        }
+        if let Some(contrast_threshold) = self.contrast_threshold {
+            settings.contrast_threshold = contrast_threshold;
+        }
+        if let Some(tone_mapper) = self.tone_mapper {
+            settings.tone_mapping.operator = tone_mapper;
+        }
+        if let Some(exposure) = self.exposure {
+            settings.tone_mapping.exposure = exposure;
+        }
+        if self.auto_exposure {
+            settings.tone_mapping.auto_exposure = true;
+        }
+        if let Some(white_point) = self.white_point {
+            settings.tone_mapping.white_point = white_point;
+        }
+    }
+    pub fn frame_path(&self, frame: u32) -> PathBuf {
+        if self.frames <= 1 {
+            return self.output.clone();
+        }
+        let stem = self
+            .output
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let name = match self.output.extension() {
+            Some(ext) => format!("{}_{:04}.{}", stem, frame, ext.to_string_lossy()),
+            None => format!("{}_{:04}", stem, frame),
+        };
+        self.output.with_file_name(name)
+    }
+    pub fn aov_path(&self, frame: u32, aov: Aov) -> PathBuf {
+        let path = self.frame_path(frame);
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let name = match path.extension() {
+            Some(ext) => format!("{}_{}.{}", stem, aov.name(), ext.to_string_lossy()),
+            None => format!("{}_{}", stem, aov.name()),
+        };
+        path.with_file_name(name)
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("missing value for {}", flag))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", flag, value))
+}
+
+fn parse_aovs(flag: &str, value: Option<String>) -> Result<Vec<Aov>, String> {
+    let value = value.ok_or_else(|| format!("missing value for {}", flag))?;
+    if value == "all" {
+        return Ok(Aov::ALL.to_vec());
+    }
+    value
+        .split(',')
+        .map(|name| {
+            Aov::parse(name.trim()).ok_or_else(|| format!("invalid value for {}: {}", flag, name))
+        })
+        .collect()
+}
+
+fn parse_vec3(flag: &str, value: Option<String>) -> Result<Vec3, String> {
+    let value = value.ok_or_else(|| format!("missing value for {}", flag))?;
+    let parts: Vec<f32> = value
+        .split(',')
+        .map(|p| p.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("invalid value for {}: {}", flag, value))?;
+    match parts[..] {
+        [x, y, z] => Ok(Vec3::new(x, y, z)),
+        _ => Err(format!("expected x,y,z for {}: {}", flag, value)),
+    }
+}
+
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--headless" => options.headless = true,
+            "--width" => options.width = parse_value(&flag, args.next())?,
+            "--height" => options.height = parse_value(&flag, args.next())?,
+            "--output" => options.output = parse_value(&flag, args.next())?,
+            "--aov" => options.aovs = parse_aovs(&flag, args.next())?,
+            "--frames" => options.frames = parse_value(&flag, args.next())?,
+            "--scene" => options.scene = Some(parse_value(&flag, args.next())?),
+            "--environment" => options.environment = Some(parse_value(&flag, args.next())?),
+            "--env-rotation" => options.env_rotation = parse_value(&flag, args.next())?,
+            "--env-intensity" => options.env_intensity = parse_value(&flag, args.next())?,
+            "--camera" => options.camera_pos = Some(parse_vec3(&flag, args.next())?),
+            "--yaw" => options.yaw = Some(parse_value(&flag, args.next())?),
+            "--pitch" => options.pitch = Some(parse_value(&flag, args.next())?),
+            "--yaw-step" => options.yaw_step = parse_value(&flag, args.next())?,
+            "--max-bounces" => options.max_bounces = Some(parse_value(&flag, args.next())?),
+            "--integrator" => {
+                let value = args.next();
+                match value.as_deref().and_then(integrator::find) {
+                    Some(integrator) => options.integrator = Some(integrator),
+                    None => {
+                        return Err(format!(
+                            "invalid value for --integrator: {}",
+                            value.as_deref().unwrap_or("<missing>")
+                        ));
+                    }
+                }
+            }
+            "--shadow-samples" => options.shadow_samples = Some(parse_value(&flag, args.next())?),
+            "--spp" => options.samples_per_pixel = Some(parse_value(&flag, args.next())?),
+            "--adaptive" => options.adaptive_samples = Some(parse_value(&flag, args.next())?),
+            "--contrast" => options.contrast_threshold = Some(parse_value(&flag, args.next())?),
+            "--tonemap" => {
+                let value = args.next();
+                match value.as_deref().and_then(ToneMapper::parse) {
+                    Some(operator) => options.tone_mapper = Some(operator),
+                    None => {
+                        return Err(format!(
+                            "invalid value for --tonemap: {}",
+                            value.as_deref().unwrap_or("<missing>")
+                        ));
+                    }
+                }
+            }
+            "--exposure" => options.exposure = Some(parse_value(&flag, args.next())?),
+            "--auto-exposure" => options.auto_exposure = true,
+            "--white-point" => options.white_point = Some(parse_value(&flag, args.next())?),
+            "--bvh" => {
+                options.bvh.method = match args.next().as_deref() {
+                    Some("median") => SplitMethod::Median,
+                    Some("sah") => SplitMethod::Sah,
+                    other => {
+                        return Err(format!(
+                            "invalid value for --bvh: {}",
+                            other.unwrap_or("<missing>")
+                        ));
+                    }
+                }
+            }
+            "--sah-bins" => options.bvh.bins = parse_value(&flag, args.next())?,
+            "--leaf-cost" => options.bvh.leaf_cost = parse_value(&flag, args.next())?,
+            "--traversal-cost" => options.bvh.traversal_cost = parse_value(&flag, args.next())?,
+            "--max-leaf-size" => options.bvh.max_leaf_size = parse_value(&flag, args.next())?,
+            "--rebuild-threshold" => {
+                options.bvh.rebuild_threshold = parse_value(&flag, args.next())?
+            }
+            _ => return Err(format!("unknown argument: {}", flag)),
+        }
+    }
+
+    if options.width == 0 || options.height == 0 {
+        return Err("width and height must be greater than zero".to_string());
+    }
+    Ok(options)
+}
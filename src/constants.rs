@@ -4,6 +4,17 @@ pub const BUFFER_HEIGHT: usize = 108 * UPSCALING_CONST;
 pub const WINDOW_WIDTH: usize = 192 * UPSCALING_CONST;
 pub const WINDOW_HEIGHT: usize = 108 * UPSCALING_CONST;
 pub const MAX_BOUNCES: u8 = 2;
+pub const PATH_MAX_BOUNCES: u8 = 64;
+pub const RUSSIAN_ROULETTE_DEPTH: u8 = 3;
 pub const MOVE_SPEED: f32 = 0.1;
 pub const MOUSE_SENSITIVITY: f32 = 0.5;
-pub const GAMMA: f32 = 2.2;
+pub const RAY_EPSILON: f32 = 1e-3;
+pub const SHADOW_SAMPLES: u32 = 8;
+pub const SAMPLES_PER_PIXEL: u32 = 1;
+pub const MAX_ACCUMULATED_SAMPLES: u32 = 1024;
+pub const ADAPTIVE_SAMPLES: u32 = 0;
+pub const ADAPTIVE_MIN_SAMPLES: u32 = 4;
+pub const CONTRAST_THRESHOLD: f32 = 0.15;
+pub const EDGE_NORMAL_COS: f32 = 0.9;
+pub const EDGE_DEPTH_RATIO: f32 = 0.05;
+pub const CREASE_ANGLE_COS: f32 = 0.5;
@@ -0,0 +1,149 @@
+use crate::mat4::Mat4;
+use crate::texture::{FilterMode, Texture, WrapMode};
+use crate::vec3::Vec3;
+use rayon::prelude::*;
+use std::f32::consts::PI;
+
+pub enum EnvironmentMap {
+    Constant(Vec3),
+    Equirectangular(Texture),
+    Cube(Box<[Texture; 6]>),
+}
+
+pub struct Environment {
+    pub map: EnvironmentMap,
+    pub intensity: f32,
+    to_local: Mat4,
+    irradiance: Option<Texture>,
+}
+
+const IRRADIANCE_WIDTH: usize = 32;
+const IRRADIANCE_HEIGHT: usize = 16;
+const IRRADIANCE_SOURCE_WIDTH: usize = 64;
+const IRRADIANCE_SOURCE_HEIGHT: usize = 32;
+
+fn equirect_uv(d: Vec3) -> (f32, f32) {
+    (
+        0.5 + d.z.atan2(d.x) / (2.0 * PI),
+        0.5 + d.y.clamp(-1.0, 1.0).asin() / PI,
+    )
+}
+
+fn equirect_direction(u: f32, v: f32) -> Vec3 {
+    let phi = (u - 0.5) * 2.0 * PI;
+    let theta = (v - 0.5) * PI;
+    Vec3::new(
+        theta.cos() * phi.cos(),
+        theta.sin(),
+        theta.cos() * phi.sin(),
+    )
+}
+
+impl EnvironmentMap {
+    fn sample(&self, d: Vec3) -> Vec3 {
+        match self {
+            EnvironmentMap::Constant(color) => *color,
+            EnvironmentMap::Equirectangular(texture) => {
+                let (u, v) = equirect_uv(d);
+                let half_texel = 0.5 / texture.height as f32;
+                texture.sample(u, v.clamp(half_texel, 1.0 - half_texel))
+            }
+            EnvironmentMap::Cube(faces) => {
+                let (ax, ay, az) = (d.x.abs(), d.y.abs(), d.z.abs());
+                let (face, sc, tc, ma) = if ax >= ay && ax >= az {
+                    if d.x > 0.0 {
+                        (0, -d.z, -d.y, ax)
+                    } else {
+                        (1, d.z, -d.y, ax)
+                    }
+                } else if ay >= az {
+                    if d.y > 0.0 {
+                        (2, d.x, d.z, ay)
+                    } else {
+                        (3, d.x, -d.z, ay)
+                    }
+                } else if d.z > 0.0 {
+                    (4, d.x, -d.y, az)
+                } else {
+                    (5, -d.x, -d.y, az)
+                };
+                let s = 0.5 * (sc / ma + 1.0);
+                let t = 0.5 * (tc / ma + 1.0);
+                faces[face].sample(s, 1.0 - t)
+            }
+        }
+    }
+
+    fn irradiance(&self) -> Texture {
+        let (sw, sh) = (IRRADIANCE_SOURCE_WIDTH, IRRADIANCE_SOURCE_HEIGHT);
+        let texel_angle = (2.0 * PI / sw as f32) * (PI / sh as f32);
+        let source: Vec<(Vec3, Vec3)> = (0..sw * sh)
+            .map(|idx| {
+                let u = ((idx % sw) as f32 + 0.5) / sw as f32;
+                let v = ((idx / sw) as f32 + 0.5) / sh as f32;
+                let d = equirect_direction(u, v);
+                let solid_angle = texel_angle * ((v - 0.5) * PI).cos();
+                (d, self.sample(d) * solid_angle)
+            })
+            .collect();
+
+        let (w, h) = (IRRADIANCE_WIDTH, IRRADIANCE_HEIGHT);
+        let data: Vec<Vec3> = (0..w * h)
+            .into_par_iter()
+            .map(|idx| {
+                let u = ((idx % w) as f32 + 0.5) / w as f32;
+                let v = 1.0 - ((idx / w) as f32 + 0.5) / h as f32;
+                let n = equirect_direction(u, v);
+                source
+                    .iter()
+                    .fold(Vec3::new(0.0, 0.0, 0.0), |sum, &(d, radiance)| {
+                        sum + radiance * n.dot(d).max(0.0)
+                    })
+                    * (1.0 / PI)
+            })
+            .collect();
+
+        Texture {
+            width: w,
+            height: h,
+            data,
+            wrap: WrapMode::Repeat,
+            filter: FilterMode::Bilinear,
+        }
+    }
+}
+
+impl Environment {
+    pub fn constant(color: Vec3) -> Self {
+        Self {
+            map: EnvironmentMap::Constant(color),
+            intensity: 1.0,
+            to_local: Mat4::identity(),
+            irradiance: None,
+        }
+    }
+
+    pub fn new(map: EnvironmentMap, rotation: Vec3, intensity: f32, lighting: bool) -> Self {
+        let rotation = Mat4::from_trs(Vec3::new(0.0, 0.0, 0.0), rotation, Vec3::new(1.0, 1.0, 1.0));
+        let irradiance = lighting.then(|| map.irradiance());
+        Self {
+            map,
+            intensity,
+            to_local: rotation.affine_inverse().expect("rotation is invertible"),
+            irradiance,
+        }
+    }
+
+    pub fn radiance(&self, direction: Vec3) -> Vec3 {
+        let local = self.to_local.transform_vector(direction).normalize();
+        self.map.sample(local) * self.intensity
+    }
+
+    pub fn irradiance(&self, normal: Vec3) -> Vec3 {
+        let Some(irradiance) = &self.irradiance else {
+            return Vec3::new(0.0, 0.0, 0.0);
+        };
+        let (u, v) = equirect_uv(self.to_local.transform_vector(normal));
+        irradiance.sample(u, v) * self.intensity
+    }
+}
@@ -0,0 +1,498 @@
+use crate::tonemap::ToneMapping;
+use crate::vec3::Vec3;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+fn invalid_data(path: &Path, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}: {}", path.display(), message),
+    )
+}
+
+fn srgb_to_linear(c: u8) -> f32 {
+    srgb_decode(c as f32 / 255.0)
+}
+
+fn srgb_decode(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn to_srgb8(buffer: &[Vec3], tone_mapping: &ToneMapping) -> Vec<u8> {
+    let scale = tone_mapping.exposure_scale(buffer);
+    buffer
+        .iter()
+        .flat_map(|c| {
+            let packed = tone_mapping.encode(*c, scale);
+            [(packed >> 16) as u8, (packed >> 8) as u8, packed as u8]
+        })
+        .collect()
+}
+
+pub fn write_ppm(
+    path: &Path,
+    width: usize,
+    height: usize,
+    buffer: &[Vec3],
+    tone_mapping: &ToneMapping,
+) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write!(out, "P6\n{} {}\n255\n", width, height)?;
+    out.write_all(&to_srgb8(buffer, tone_mapping))?;
+    out.flush()
+}
+
+pub fn write_png(
+    path: &Path,
+    width: usize,
+    height: usize,
+    buffer: &[Vec3],
+    tone_mapping: &ToneMapping,
+) -> io::Result<()> {
+    let out = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(out, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&to_srgb8(buffer, tone_mapping))?;
+    writer.finish()?;
+    Ok(())
+}
+
+pub fn write_pfm(path: &Path, width: usize, height: usize, buffer: &[Vec3]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write!(out, "PF\n{} {}\n-1.0\n", width, height)?;
+    for row in buffer.chunks(width).rev() {
+        for c in row {
+            out.write_all(&c.x.to_le_bytes())?;
+            out.write_all(&c.y.to_le_bytes())?;
+            out.write_all(&c.z.to_le_bytes())?;
+        }
+    }
+    out.flush()
+}
+
+pub fn write_image(
+    path: &Path,
+    width: usize,
+    height: usize,
+    buffer: &[Vec3],
+    tone_mapping: &ToneMapping,
+) -> io::Result<()> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("ppm") => write_ppm(path, width, height, buffer, tone_mapping),
+        Some("png") => write_png(path, width, height, buffer, tone_mapping),
+        Some("pfm") => write_pfm(path, width, height, buffer),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unsupported output format: {}", path.display()),
+        )),
+    }
+}
+
+pub fn read_ppm(path: &Path) -> io::Result<(usize, usize, Vec<Vec3>)> {
+    let bytes = fs::read(path)?;
+    let mut pos = 0;
+    let mut header = Vec::new();
+    while header.len() < 4 {
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if pos < bytes.len() && bytes[pos] == b'#' {
+            while pos < bytes.len() && bytes[pos] != b'\n' {
+                pos += 1;
+            }
+            continue;
+        }
+        let token_start = pos;
+        while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if token_start == pos {
+            return Err(invalid_data(path, "truncated PPM header"));
+        }
+        header.push(String::from_utf8_lossy(&bytes[token_start..pos]).into_owned());
+    }
+
+    let parse = |s: &str| {
+        s.parse::<usize>()
+            .map_err(|_| invalid_data(path, "invalid PPM header"))
+    };
+    let (width, height, max_value) = (parse(&header[1])?, parse(&header[2])?, parse(&header[3])?);
+    if max_value == 0 || max_value > 65535 {
+        return Err(invalid_data(path, "invalid PPM maximum value"));
+    }
+
+    let samples: Vec<usize> = match header[0].as_str() {
+        "P6" if max_value > 255 => bytes
+            .get(pos + 1..)
+            .unwrap_or_default()
+            .chunks_exact(2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+            .collect(),
+        "P6" => bytes
+            .get(pos + 1..)
+            .unwrap_or_default()
+            .iter()
+            .map(|&b| b as usize)
+            .collect(),
+        "P3" => String::from_utf8_lossy(&bytes[pos..])
+            .split_ascii_whitespace()
+            .map(parse)
+            .collect::<io::Result<_>>()?,
+        _ => return Err(invalid_data(path, "unsupported PPM magic number")),
+    };
+    if samples.len() < width * height * 3 {
+        return Err(invalid_data(path, "truncated PPM pixel data"));
+    }
+    if samples
+        .iter()
+        .take(width * height * 3)
+        .any(|&v| v > max_value)
+    {
+        return Err(invalid_data(path, "PPM sample exceeds maximum value"));
+    }
+    let scale = |v: usize| srgb_decode(v as f32 / max_value as f32);
+
+    let data = samples
+        .chunks_exact(3)
+        .take(width * height)
+        .map(|c| Vec3::new(scale(c[0]), scale(c[1]), scale(c[2])))
+        .collect();
+    Ok((width, height, data))
+}
+
+pub fn read_png(path: &Path) -> io::Result<(usize, usize, Vec<Vec3>)> {
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size().unwrap_or(0)];
+    let info = reader.next_frame(&mut buf)?;
+
+    let channels = info.color_type.samples();
+    let data = buf[..info.buffer_size()]
+        .chunks_exact(info.line_size)
+        .flat_map(|line| line[..info.width as usize * channels].chunks_exact(channels))
+        .map(|px| match channels {
+            1 | 2 => {
+                let l = srgb_to_linear(px[0]);
+                Vec3::new(l, l, l)
+            }
+            _ => Vec3::new(
+                srgb_to_linear(px[0]),
+                srgb_to_linear(px[1]),
+                srgb_to_linear(px[2]),
+            ),
+        })
+        .collect();
+    Ok((info.width as usize, info.height as usize, data))
+}
+
+pub fn read_pfm(path: &Path) -> io::Result<(usize, usize, Vec<Vec3>)> {
+    let bytes = fs::read(path)?;
+    let mut lines = Vec::new();
+    let mut pos = 0;
+    while lines.len() < 3 {
+        let end = bytes[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| invalid_data(path, "truncated PFM header"))?;
+        lines.push(
+            String::from_utf8_lossy(&bytes[pos..pos + end])
+                .trim()
+                .to_string(),
+        );
+        pos += end + 1;
+    }
+
+    let channels = match lines[0].as_str() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(invalid_data(path, "unsupported PFM magic number")),
+    };
+    let mut dims = lines[1].split_ascii_whitespace().map(str::parse::<usize>);
+    let (Some(Ok(width)), Some(Ok(height))) = (dims.next(), dims.next()) else {
+        return Err(invalid_data(path, "invalid PFM dimensions"));
+    };
+    let scale: f32 = lines[2]
+        .parse()
+        .map_err(|_| invalid_data(path, "invalid PFM scale"))?;
+    let little_endian = scale < 0.0;
+
+    let row_bytes = width * channels * 4;
+    let pixels = bytes
+        .get(pos..pos + row_bytes * height)
+        .ok_or_else(|| invalid_data(path, "truncated PFM pixel data"))?;
+    let value = |b: &[u8]| {
+        let b = [b[0], b[1], b[2], b[3]];
+        if little_endian {
+            f32::from_le_bytes(b)
+        } else {
+            f32::from_be_bytes(b)
+        }
+    };
+    let data = pixels
+        .chunks_exact(row_bytes)
+        .rev()
+        .flat_map(|row| row.chunks_exact(channels * 4))
+        .map(|px| match channels {
+            1 => {
+                let l = value(px);
+                Vec3::new(l, l, l)
+            }
+            _ => Vec3::new(value(&px[0..4]), value(&px[4..8]), value(&px[8..12])),
+        })
+        .collect();
+    Ok((width, height, data))
+}
+
+fn rgbe_to_linear(rgbe: [u8; 4]) -> Vec3 {
+    if rgbe[3] == 0 {
+        return Vec3::new(0.0, 0.0, 0.0);
+    }
+    let f = (rgbe[3] as f32 - 136.0).exp2();
+    Vec3::new(
+        (rgbe[0] as f32 + 0.5) * f,
+        (rgbe[1] as f32 + 0.5) * f,
+        (rgbe[2] as f32 + 0.5) * f,
+    )
+}
+
+pub fn read_hdr(path: &Path) -> io::Result<(usize, usize, Vec<Vec3>)> {
+    let bytes = fs::read(path)?;
+    let mut pos = 0;
+    let next_line = |pos: &mut usize| {
+        let end = bytes[*pos..].iter().position(|&b| b == b'\n')?;
+        let line = String::from_utf8_lossy(&bytes[*pos..*pos + end]).into_owned();
+        *pos += end + 1;
+        Some(line)
+    };
+
+    let magic = next_line(&mut pos).unwrap_or_default();
+    if !magic.starts_with("#?") {
+        return Err(invalid_data(path, "missing Radiance HDR signature"));
+    }
+    loop {
+        let line = next_line(&mut pos).ok_or_else(|| invalid_data(path, "truncated HDR header"))?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=")
+            && format != "32-bit_rle_rgbe"
+        {
+            return Err(invalid_data(path, "only RGBE HDR files are supported"));
+        }
+    }
+    let resolution = next_line(&mut pos).unwrap_or_default();
+    let parts: Vec<&str> = resolution.split_ascii_whitespace().collect();
+    let (height, width) = match parts.as_slice() {
+        ["-Y", h, "+X", w] => (h.parse::<usize>(), w.parse::<usize>()),
+        _ => return Err(invalid_data(path, "unsupported HDR orientation")),
+    };
+    let (Ok(height), Ok(width)) = (height, width) else {
+        return Err(invalid_data(path, "invalid HDR dimensions"));
+    };
+
+    let truncated = || invalid_data(path, "truncated HDR pixel data");
+    let mut data = Vec::with_capacity(width * height);
+    let mut scanline = vec![[0_u8; 4]; width];
+    for _ in 0..height {
+        let header = bytes.get(pos..pos + 4).ok_or_else(truncated)?;
+        let rle = (8..32768).contains(&width)
+            && header[0] == 2
+            && header[1] == 2
+            && ((header[2] as usize) << 8 | header[3] as usize) == width;
+        if rle {
+            pos += 4;
+            for channel in 0..4 {
+                let mut x = 0;
+                while x < width {
+                    let count = *bytes.get(pos).ok_or_else(truncated)? as usize;
+                    pos += 1;
+                    if count > 128 {
+                        let run = count - 128;
+                        let value = *bytes.get(pos).ok_or_else(truncated)?;
+                        pos += 1;
+                        if x + run > width {
+                            return Err(invalid_data(path, "corrupt HDR run length"));
+                        }
+                        for px in &mut scanline[x..x + run] {
+                            px[channel] = value;
+                        }
+                        x += run;
+                    } else {
+                        if count == 0 || x + count > width {
+                            return Err(invalid_data(path, "corrupt HDR run length"));
+                        }
+                        let values = bytes.get(pos..pos + count).ok_or_else(truncated)?;
+                        for (px, &value) in scanline[x..x + count].iter_mut().zip(values) {
+                            px[channel] = value;
+                        }
+                        pos += count;
+                        x += count;
+                    }
+                }
+            }
+        } else {
+            let raw = bytes.get(pos..pos + width * 4).ok_or_else(truncated)?;
+            for (px, rgbe) in scanline.iter_mut().zip(raw.chunks_exact(4)) {
+                *px = [rgbe[0], rgbe[1], rgbe[2], rgbe[3]];
+            }
+            pos += width * 4;
+        }
+        data.extend(scanline.iter().map(|&rgbe| rgbe_to_linear(rgbe)));
+    }
+    Ok((width, height, data))
+}
+
+pub fn read_image(path: &Path) -> io::Result<(usize, usize, Vec<Vec3>)> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("ppm") => read_ppm(path),
+        Some("png") => read_png(path),
+        Some("pfm") => read_pfm(path),
+        Some("hdr") => read_hdr(path),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unsupported image format: {}", path.display()),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tonemap::ToneMapper;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("raytracer-{}-{}", std::process::id(), name))
+    }
+
+    fn gradient(width: usize, height: usize) -> Vec<Vec3> {
+        (0..width * height)
+            .map(|i| {
+                let t = i as f32 / (width * height - 1) as f32;
+                Vec3::new(t, 1.0 - t, 0.5 * t)
+            })
+            .collect()
+    }
+
+    fn assert_close(a: &[Vec3], b: &[Vec3], tolerance: f32) {
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter().zip(b) {
+            assert!(
+                (x.x - y.x).abs() <= tolerance
+                    && (x.y - y.y).abs() <= tolerance
+                    && (x.z - y.z).abs() <= tolerance,
+                "{:?} != {:?}",
+                (x.x, x.y, x.z),
+                (y.x, y.y, y.z)
+            );
+        }
+    }
+
+    fn linear_to_rgbe(c: Vec3) -> [u8; 4] {
+        let max = c.max_component();
+        if max < 1e-32 {
+            return [0; 4];
+        }
+        let exponent = max.log2().floor() as i32 + 1;
+        let scale = 256.0 / (exponent as f32).exp2();
+        [
+            (c.x * scale) as u8,
+            (c.y * scale) as u8,
+            (c.z * scale) as u8,
+            (exponent + 128) as u8,
+        ]
+    }
+
+    #[test]
+    fn ppm_round_trip() {
+        let path = temp_path("round-trip.ppm");
+        let buffer = gradient(7, 5);
+        let tone_mapping = ToneMapping {
+            operator: ToneMapper::Clamp,
+            ..ToneMapping::default()
+        };
+        write_ppm(&path, 7, 5, &buffer, &tone_mapping).unwrap();
+        let (width, height, data) = read_ppm(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((width, height), (7, 5));
+        assert_close(&buffer, &data, 0.01);
+    }
+
+    #[test]
+    fn ppm_reads_16_bit_samples() {
+        let path = temp_path("16-bit.ppm");
+        let mut bytes = b"P6\n2 1\n65535\n".to_vec();
+        for v in [0_u16, 32768, 65535, 65535, 1000, 0] {
+            bytes.extend_from_slice(&v.to_be_bytes());
+        }
+        fs::write(&path, bytes).unwrap();
+        let (width, height, data) = read_ppm(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((width, height), (2, 1));
+        let expected = [
+            Vec3::new(0.0, srgb_decode(32768.0 / 65535.0), 1.0),
+            Vec3::new(1.0, srgb_decode(1000.0 / 65535.0), 0.0),
+        ];
+        assert_close(&expected, &data, 1e-6);
+    }
+
+    #[test]
+    fn ppm_rejects_samples_above_max_value() {
+        let path = temp_path("out-of-range.ppm");
+        fs::write(&path, "P3\n1 1\n100\n50 101 0\n").unwrap();
+        let error = read_ppm(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn pfm_round_trip() {
+        let path = temp_path("round-trip.pfm");
+        let buffer: Vec<Vec3> = gradient(6, 4).into_iter().map(|c| c * 20.0).collect();
+        write_pfm(&path, 6, 4, &buffer).unwrap();
+        let (width, height, data) = read_pfm(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((width, height), (6, 4));
+        assert_close(&buffer, &data, 0.0);
+    }
+
+    #[test]
+    fn hdr_reads_flat_and_run_length_scanlines() {
+        let path = temp_path("round-trip.hdr");
+        let width = 9;
+        let buffer: Vec<Vec3> = gradient(width, 2).into_iter().map(|c| c * 8.0).collect();
+        let mut bytes =
+            format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X {}\n", width).into_bytes();
+        let flat: Vec<[u8; 4]> = buffer[..width].iter().map(|&c| linear_to_rgbe(c)).collect();
+        bytes.extend(flat.iter().flatten());
+        let rle: Vec<[u8; 4]> = buffer[width..].iter().map(|&c| linear_to_rgbe(c)).collect();
+        bytes.extend_from_slice(&[2, 2, 0, width as u8]);
+        for channel in 0..4 {
+            bytes.push(width as u8);
+            bytes.extend(rle.iter().map(|px| px[channel]));
+        }
+        fs::write(&path, bytes).unwrap();
+        let (w, h, data) = read_hdr(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((w, h), (width, 2));
+        for (expected, actual) in buffer.iter().zip(&data) {
+            let tolerance = expected.max_component() / 128.0;
+            assert_close(&[*expected], &[*actual], tolerance);
+        }
+    }
+}
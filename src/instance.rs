@@ -0,0 +1,129 @@
+use crate::aabb::Aabb;
+use crate::bvh::{Bvh, BvhConfig};
+use crate::light::{Light, MeshLight, SphereLight, TriangleLight};
+use crate::mat4::Mat4;
+use crate::objects::{Hit, Object};
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+use std::sync::Arc;
+
+pub struct Geometry {
+    pub objects: Vec<Object>,
+    pub bvh: Bvh,
+}
+
+impl Geometry {
+    pub fn new(objects: Vec<Object>, config: &BvhConfig) -> Self {
+        let bvh = Bvh::build(&objects, config);
+        Self { objects, bvh }
+    }
+    pub fn has_sphere_lights(&self) -> bool {
+        self.objects
+            .iter()
+            .flat_map(Object::emitters)
+            .any(|light| matches!(light, Light::Sphere(_)))
+    }
+}
+
+pub struct Instance {
+    pub geometry: Arc<Geometry>,
+    transform: Mat4,
+    inverse: Mat4,
+}
+
+impl Instance {
+    pub fn new(geometry: Arc<Geometry>, transform: Mat4) -> Option<Self> {
+        let inverse = transform.affine_inverse()?;
+        Some(Self {
+            geometry,
+            transform,
+            inverse,
+        })
+    }
+    pub fn set_transform(&mut self, transform: Mat4) -> bool {
+        let Some(inverse) = transform.affine_inverse() else {
+            return false;
+        };
+        self.transform = transform;
+        self.inverse = inverse;
+        true
+    }
+    pub fn aabb(&self) -> Aabb {
+        let local = self.geometry.bvh.nodes[0].aabb;
+        let mut aabb = Aabb::empty();
+        for i in 0..8 {
+            let corner = Vec3::new(
+                if i & 1 == 0 { local.min.x } else { local.max.x },
+                if i & 2 == 0 { local.min.y } else { local.max.y },
+                if i & 4 == 0 { local.min.z } else { local.max.z },
+            );
+            aabb.grow(self.transform.transform_point(corner));
+        }
+        aabb
+    }
+    fn local_ray(&self, ray: &Ray) -> Ray {
+        Ray::with_interval(
+            self.inverse.transform_point(ray.origin),
+            self.inverse.transform_vector(ray.direction),
+            ray.t_min,
+            ray.t_max,
+        )
+    }
+    pub fn occluded(&self, ray: &Ray) -> bool {
+        self.geometry
+            .bvh
+            .occluded(&self.local_ray(ray), &self.geometry.objects)
+    }
+    pub fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        let local = self.local_ray(ray);
+        let hit = self
+            .geometry
+            .bvh
+            .closest_hit(&local, &self.geometry.objects)?;
+        Some(Hit {
+            normal: self
+                .inverse
+                .transform_normal_transposed(hit.normal)
+                .normalize(),
+            geometric_normal: self
+                .inverse
+                .transform_normal_transposed(hit.geometric_normal)
+                .normalize(),
+            ..hit
+        })
+    }
+    fn transform_triangle(&self, t: &TriangleLight) -> TriangleLight {
+        TriangleLight {
+            v0: self.transform.transform_point(t.v0),
+            v1: self.transform.transform_point(t.v1),
+            v2: self.transform.transform_point(t.v2),
+            emission: t.emission,
+        }
+    }
+    pub fn emitters(&self) -> Vec<Light> {
+        self.geometry
+            .objects
+            .iter()
+            .flat_map(Object::emitters)
+            .filter_map(|light| match light {
+                Light::Sphere(s) => Some(Light::Sphere(SphereLight {
+                    center: self.transform.transform_point(s.center),
+                    radius: self
+                        .transform
+                        .transform_vector(Vec3::new(s.radius, 0.0, 0.0))
+                        .len(),
+                    emission: s.emission,
+                    geometric: s.geometric,
+                })),
+                Light::Triangle(t) => Some(Light::Triangle(self.transform_triangle(&t))),
+                Light::Mesh(m) => Some(Light::Mesh(MeshLight::new(
+                    m.triangles
+                        .iter()
+                        .map(|t| self.transform_triangle(t))
+                        .collect(),
+                ))),
+                _ => None,
+            })
+            .collect()
+    }
+}
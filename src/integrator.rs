@@ -0,0 +1,40 @@
+use crate::objects::Intersection;
+use crate::path_tracer::PathTracer;
+use crate::ray::Ray;
+use crate::rng::Rng;
+use crate::scene::{RenderSettings, Scene};
+use crate::vec3::Vec3;
+use crate::whitted::Whitted;
+
+pub trait Integrator: Sync {
+    fn name(&self) -> &'static str;
+    fn shade(
+        &self,
+        ray: &Ray,
+        hit: Option<Intersection>,
+        scene: &Scene,
+        settings: &RenderSettings,
+        rng: &mut Rng,
+    ) -> Vec3;
+    fn radiance(&self, ray: &Ray, scene: &Scene, settings: &RenderSettings, rng: &mut Rng) -> Vec3 {
+        let hit = scene.bvh.intersect(ray, &scene.objects);
+        self.shade(ray, hit, scene, settings, rng)
+    }
+}
+
+pub const INTEGRATORS: [&dyn Integrator; 2] = [&Whitted, &PathTracer];
+
+pub fn find(name: &str) -> Option<&'static dyn Integrator> {
+    INTEGRATORS
+        .iter()
+        .copied()
+        .find(|integrator| integrator.name() == name)
+}
+
+pub fn next(current: &dyn Integrator) -> &'static dyn Integrator {
+    let i = INTEGRATORS
+        .iter()
+        .position(|integrator| integrator.name() == current.name())
+        .unwrap_or(0);
+    INTEGRATORS[(i + 1) % INTEGRATORS.len()]
+}
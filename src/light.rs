@@ -0,0 +1,323 @@
+use crate::rng::Rng;
+use crate::vec3::Vec3;
+use std::f32::consts::PI;
+
+pub struct LightSample {
+    pub direction: Vec3,
+    pub distance: f32,
+    pub radiance: Vec3,
+}
+
+pub struct PointLight {
+    pub position: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+pub struct DirectionalLight {
+    pub direction: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+pub struct SpotLight {
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+    pub cone_angle: f32,
+    pub falloff: f32,
+}
+
+pub struct HemisphereLight {
+    pub sky_color: Vec3,
+    pub ground_color: Vec3,
+    pub intensity: f32,
+}
+
+pub struct SphereLight {
+    pub center: Vec3,
+    pub radius: f32,
+    pub emission: Vec3,
+    pub geometric: bool,
+}
+
+pub struct RectLight {
+    pub corner: Vec3,
+    pub edge_u: Vec3,
+    pub edge_v: Vec3,
+    pub emission: Vec3,
+}
+
+pub struct DiscLight {
+    pub center: Vec3,
+    pub normal: Vec3,
+    pub radius: f32,
+    pub emission: Vec3,
+}
+
+pub struct TriangleLight {
+    pub v0: Vec3,
+    pub v1: Vec3,
+    pub v2: Vec3,
+    pub emission: Vec3,
+}
+
+pub struct MeshLight {
+    pub triangles: Vec<TriangleLight>,
+    cdf: Vec<f32>,
+}
+
+pub enum Light {
+    Point(PointLight),
+    Directional(DirectionalLight),
+    Spot(SpotLight),
+    Hemisphere(HemisphereLight),
+    Sphere(SphereLight),
+    Rect(RectLight),
+    Disc(DiscLight),
+    Triangle(TriangleLight),
+    Mesh(MeshLight),
+}
+
+fn area_sample(
+    point: Vec3,
+    light_point: Vec3,
+    light_normal: Vec3,
+    area: f32,
+    emission: Vec3,
+    two_sided: bool,
+) -> Option<LightSample> {
+    let light_vec = light_point - point;
+    let dist_sq = light_vec.len_sq();
+    let distance = dist_sq.sqrt();
+    let direction = light_vec * (1.0 / distance);
+    let mut cos_light = -direction.dot(light_normal);
+    if two_sided {
+        cos_light = cos_light.abs();
+    }
+    if cos_light <= 0.0 {
+        return None;
+    }
+    Some(LightSample {
+        direction,
+        distance,
+        radiance: emission * (cos_light * area / dist_sq),
+    })
+}
+
+impl PointLight {
+    pub fn sample(&self, point: Vec3) -> LightSample {
+        let light_vec = self.position - point;
+        let dist_sq = light_vec.len_sq();
+        let distance = dist_sq.sqrt();
+        LightSample {
+            direction: light_vec * (1.0 / distance),
+            distance,
+            radiance: self.color * (self.intensity * PI / dist_sq),
+        }
+    }
+}
+
+impl DirectionalLight {
+    pub fn sample(&self) -> LightSample {
+        LightSample {
+            direction: -self.direction.normalize(),
+            distance: f32::INFINITY,
+            radiance: self.color * (self.intensity * PI),
+        }
+    }
+}
+
+impl SpotLight {
+    pub fn sample(&self, point: Vec3) -> Option<LightSample> {
+        let light_vec = self.position - point;
+        let dist_sq = light_vec.len_sq();
+        let distance = dist_sq.sqrt();
+        let direction = light_vec * (1.0 / distance);
+
+        let cos_theta = (-direction).dot(self.direction.normalize());
+        let cos_outer = self.cone_angle.to_radians().cos();
+        let cos_inner = (self.cone_angle - self.falloff).max(0.0).to_radians().cos();
+        if cos_theta <= cos_outer {
+            return None;
+        }
+        let x = ((cos_theta - cos_outer) / (cos_inner - cos_outer).max(1e-6)).clamp(0.0, 1.0);
+        let spot = x * x * (3.0 - 2.0 * x);
+
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: self.color * (self.intensity * PI * spot / dist_sq),
+        })
+    }
+}
+
+impl HemisphereLight {
+    pub fn irradiance(&self, normal: Vec3) -> Vec3 {
+        let t = 0.5 * (normal.y + 1.0);
+        (self.ground_color * (1.0 - t) + self.sky_color * t) * self.intensity
+    }
+}
+
+impl SphereLight {
+    pub fn sample(&self, point: Vec3, rng: &mut Rng) -> Option<LightSample> {
+        let to_center = self.center - point;
+        let dist_sq = to_center.len_sq();
+        let radius_sq = self.radius * self.radius;
+        if dist_sq > radius_sq {
+            let dist = dist_sq.sqrt();
+            let axis = to_center * (1.0 / dist);
+            let cos_max = (1.0 - radius_sq / dist_sq).max(0.0).sqrt();
+            let cos_theta = 1.0 - rng.next_f32() * (1.0 - cos_max);
+            let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+            let phi = 2.0 * PI * rng.next_f32();
+            let (tangent, bitangent) = axis.orthonormal_basis();
+            let direction = (tangent * (sin_theta * phi.cos())
+                + bitangent * (sin_theta * phi.sin())
+                + axis * cos_theta)
+                .normalize();
+            let along = dist * cos_theta;
+            let distance = along - (radius_sq - dist_sq + along * along).max(0.0).sqrt();
+            return Some(LightSample {
+                direction,
+                distance,
+                radiance: self.emission * (2.0 * PI * (1.0 - cos_max)),
+            });
+        }
+        let z = 1.0 - 2.0 * rng.next_f32();
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * rng.next_f32();
+        let normal = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+        let area = 4.0 * PI * self.radius * self.radius;
+        area_sample(
+            point,
+            self.center + normal * self.radius,
+            normal,
+            area,
+            self.emission,
+            false,
+        )
+    }
+}
+
+impl RectLight {
+    pub fn sample(&self, point: Vec3, rng: &mut Rng) -> Option<LightSample> {
+        let light_point = self.corner + self.edge_u * rng.next_f32() + self.edge_v * rng.next_f32();
+        let cross = self.edge_u.cross(self.edge_v);
+        area_sample(
+            point,
+            light_point,
+            cross.normalize(),
+            cross.len(),
+            self.emission,
+            false,
+        )
+    }
+}
+
+impl DiscLight {
+    pub fn sample(&self, point: Vec3, rng: &mut Rng) -> Option<LightSample> {
+        let normal = self.normal.normalize();
+        let (tangent, bitangent) = normal.orthonormal_basis();
+        let r = self.radius * rng.next_f32().sqrt();
+        let phi = 2.0 * PI * rng.next_f32();
+        let light_point = self.center + tangent * (r * phi.cos()) + bitangent * (r * phi.sin());
+        let area = PI * self.radius * self.radius;
+        area_sample(point, light_point, normal, area, self.emission, false)
+    }
+}
+
+impl TriangleLight {
+    fn area(&self) -> f32 {
+        0.5 * (self.v1 - self.v0).cross(self.v2 - self.v0).len()
+    }
+
+    fn sample_with_area(&self, point: Vec3, area: f32, rng: &mut Rng) -> Option<LightSample> {
+        let su = rng.next_f32().sqrt();
+        let v = rng.next_f32();
+        let b0 = 1.0 - su;
+        let b1 = su * (1.0 - v);
+        let b2 = su * v;
+        let light_point = self.v0 * b0 + self.v1 * b1 + self.v2 * b2;
+        let cross = (self.v1 - self.v0).cross(self.v2 - self.v0);
+        area_sample(
+            point,
+            light_point,
+            cross.normalize(),
+            area,
+            self.emission,
+            true,
+        )
+    }
+
+    pub fn sample(&self, point: Vec3, rng: &mut Rng) -> Option<LightSample> {
+        self.sample_with_area(point, self.area(), rng)
+    }
+}
+
+impl MeshLight {
+    pub fn new(triangles: Vec<TriangleLight>) -> Self {
+        let mut total = 0.0;
+        let cdf = triangles
+            .iter()
+            .map(|t| {
+                total += t.area();
+                total
+            })
+            .collect();
+        Self { triangles, cdf }
+    }
+
+    pub fn sample(&self, point: Vec3, rng: &mut Rng) -> Option<LightSample> {
+        let total = *self.cdf.last()?;
+        if total <= 0.0 {
+            return None;
+        }
+        let target = rng.next_f32() * total;
+        let i = self
+            .cdf
+            .partition_point(|&c| c <= target)
+            .min(self.triangles.len() - 1);
+        self.triangles[i].sample_with_area(point, total, rng)
+    }
+}
+
+impl Light {
+    pub fn sample(&self, point: Vec3, rng: &mut Rng) -> Option<LightSample> {
+        match self {
+            Light::Point(l) => Some(l.sample(point)),
+            Light::Directional(l) => Some(l.sample()),
+            Light::Spot(l) => l.sample(point),
+            Light::Hemisphere(_) => None,
+            Light::Sphere(l) => l.sample(point, rng),
+            Light::Rect(l) => l.sample(point, rng),
+            Light::Disc(l) => l.sample(point, rng),
+            Light::Triangle(l) => l.sample(point, rng),
+            Light::Mesh(l) => l.sample(point, rng),
+        }
+    }
+    pub fn is_area(&self) -> bool {
+        matches!(
+            self,
+            Light::Sphere(_)
+                | Light::Rect(_)
+                | Light::Disc(_)
+                | Light::Triangle(_)
+                | Light::Mesh(_)
+        )
+    }
+    pub fn is_geometric(&self) -> bool {
+        match self {
+            Light::Sphere(s) => s.geometric,
+            Light::Triangle(_) | Light::Mesh(_) => true,
+            _ => false,
+        }
+    }
+    pub fn ambient(&self, normal: Vec3) -> Vec3 {
+        match self {
+            Light::Hemisphere(l) => l.irradiance(normal),
+            _ => Vec3::new(0.0, 0.0, 0.0),
+        }
+    }
+}
@@ -1,127 +1,234 @@
-use minifb::{Key, MouseMode, Window, WindowOptions};
+use minifb::{Key, KeyRepeat, MouseMode, Window, WindowOptions};
 use rayon::prelude::*;
 
 mod aabb;
+mod accumulator;
+mod aov;
+mod brdf;
 mod bvh;
 mod camera;
+mod cli;
 mod constants;
+mod environment;
+mod image_io;
+mod instance;
+mod integrator;
+mod light;
+mod mat4;
 mod material;
+mod mesh;
 mod objects;
+mod path_tracer;
 mod ray;
+mod rng;
+mod scene;
+mod scene_file;
+mod surface;
+mod texture;
+mod tonemap;
 mod vec3;
+mod whitted;
 
-use bvh::Bvh;
+use accumulator::Accumulator;
+use aov::Aov;
+use bvh::BvhConfig;
 use camera::Camera;
+use cli::{Options, USAGE, parse_args};
 use constants::*;
-use material::Material;
-use objects::{load_obj, Object, PointLight, Sphere, Triangle};
+use environment::{Environment, EnvironmentMap};
+use image_io::write_image;
+use light::PointLight;
+use material::{Material, Shading};
+use mesh::load_obj;
+use objects::{Object, Sphere, Triangle};
 use ray::Ray;
+use rng::Rng;
+use scene::{RenderSettings, Scene};
+use scene_file::load_scene;
+use std::env;
+use std::process;
+use std::time::Instant;
+use texture::{FilterMode, Texture, WrapMode};
+use tonemap::linear_to_srgb;
 use vec3::Vec3;
 
-fn compute_pixel_color(mut ray: Ray, objects: &[Object], bvh: &Bvh, light: &PointLight) -> u32 {
-    let mut final_color = Vec3::new(0.0, 0.0, 0.0);
-    let mut throughput = Vec3::new(1.0, 1.0, 1.0);
-
-    for bounce in 0..MAX_BOUNCES {
-        if let Some(hit) = bvh.intersect(&ray, objects) {
-            final_color = final_color + throughput.mul_vec(hit.material.emission);
-
-            let mut albedo = hit.material.albedo;
-            if hit.material.checkered {
-                let size = 1.0;
-                let x = (hit.point.x * size).floor() as i32;
-                let z = (hit.point.z * size).floor() as i32;
-                if (x + z) % 2 != 0 {
-                    albedo = Vec3::new(0.1, 0.1, 0.1);
-                }
-            }
-
-            let view_dir = -ray.direction;
-            let n_dot_v = hit.normal.dot(view_dir).max(0.0);
-            let fresnel =
-                hit.material.specular + (1.0 - hit.material.specular) * (1.0 - n_dot_v).powi(5);
+fn ray_generator(camera: &Camera, width: usize, height: usize) -> impl Fn(f32, f32) -> Ray + Sync {
+    let aspect_ratio = width as f32 / height as f32;
+    let fov_scale = (90.0f32.to_radians() / 2.0).tan();
+    let (cam_forward, cam_right, cam_up) = camera.get_vectors();
+    let origin = camera.pos;
 
-            let light_vec = light.position - hit.point;
-            let dist_sq = light_vec.len_sq();
-            let dist = dist_sq.sqrt();
-            let light_dir = light_vec * (1.0 / dist);
+    move |x, y| {
+        let ndc_x = (2.0 * x / width as f32 - 1.0) * aspect_ratio * fov_scale;
+        let ndc_y = (1.0 - 2.0 * y / height as f32) * fov_scale;
+        let direction = (cam_forward + cam_right * ndc_x + cam_up * ndc_y).normalize();
+        Ray::new(origin, direction)
+    }
+}
 
-            let shadow_ray = Ray::new(hit.point + hit.normal * 0.001, light_dir);
-            let mut in_shadow = false;
+fn render_frame(
+    buffer: &mut [Vec3],
+    width: usize,
+    height: usize,
+    camera: &Camera,
+    scene: &Scene,
+    settings: &RenderSettings,
+    sample: u32,
+) {
+    if sample == 0 && settings.adaptive_samples > 0 {
+        render_adaptive(buffer, width, height, camera, scene, settings);
+        return;
+    }
+    let primary_ray = ray_generator(camera, width, height);
 
-            if let Some(shadow_hit) = bvh.intersect(&shadow_ray, objects) {
-                let dist_to_blocker_sq = (shadow_hit.point - shadow_ray.origin).len_sq();
-                if dist_to_blocker_sq < dist_sq && shadow_hit.material.emission.len() == 0.0 {
-                    in_shadow = true;
-                }
+    buffer
+        .par_chunks_mut(width)
+        .enumerate()
+        .for_each(|(j, row)| {
+            for (i, pixel) in row.iter_mut().enumerate() {
+                let mut rng = Rng::new(((sample as u64) << 32) | (j * width + i) as u64);
+                let (dx, dy) = if sample == 0 {
+                    (0.5, 0.5)
+                } else {
+                    (rng.next_f32(), rng.next_f32())
+                };
+                let ray = primary_ray(i as f32 + dx, j as f32 + dy);
+                *pixel = settings
+                    .integrator
+                    .radiance(&ray, scene, settings, &mut rng);
             }
+        });
+}
 
-            if !in_shadow {
-                let n_dot_l = hit.normal.dot(light_dir).max(0.0);
-                let attenuation = 1.0 / dist_sq;
-                let incoming_light = light.color * light.intensity * attenuation;
-
-                let halfway_dir = (light_dir + view_dir).normalize();
-                let n_dot_h = hit.normal.dot(halfway_dir).max(0.0);
-                let specular_intensity = n_dot_h.powf(hit.material.shininess);
-                let specular_color = Vec3::new(1.0, 1.0, 1.0) * specular_intensity * fresnel;
-
-                let k_diffuse = 1.0 - fresnel;
-                let diffuse_color = albedo.mul_vec(incoming_light) * n_dot_l * k_diffuse;
+fn render_aov(
+    buffer: &mut [Vec3],
+    width: usize,
+    height: usize,
+    camera: &Camera,
+    scene: &Scene,
+    aov: Aov,
+) {
+    let primary_ray = ray_generator(camera, width, height);
+    let (forward, _, _) = camera.get_vectors();
 
-                final_color = final_color
-                    + throughput.mul_vec(diffuse_color + specular_color.mul_vec(incoming_light));
+    buffer
+        .par_chunks_mut(width)
+        .enumerate()
+        .for_each(|(j, row)| {
+            for (i, pixel) in row.iter_mut().enumerate() {
+                let mut rng = Rng::new((j * width + i) as u64);
+                let ray = primary_ray(i as f32 + 0.5, j as f32 + 0.5);
+                *pixel = aov.evaluate(&ray, forward, scene, &mut rng);
             }
+        });
+}
 
-            if hit.material.specular > 0.0 || fresnel > 0.05 {
-                let reflect = ray.direction - 2.0 * ray.direction.dot(hit.normal) * hit.normal;
-                throughput = throughput * fresnel;
-                ray = Ray::new(hit.point + hit.normal * 0.001, reflect.normalize());
-            } else {
-                break;
-            }
-        } else {
-            let background = Vec3::new(0.05, 0.05, 0.1) * (0.5_f32).powi(bounce as i32);
-            final_color = final_color + throughput.mul_vec(background);
-            break;
+#[derive(Clone, Copy)]
+struct EdgeSample {
+    color: Vec3,
+    normal: Vec3,
+    depth: f32,
+}
+
+fn is_edge(a: &EdgeSample, b: &EdgeSample, contrast_threshold: f32) -> bool {
+    let perceived = |c: Vec3| linear_to_srgb(c.luminance());
+    if (perceived(a.color) - perceived(b.color)).abs() > contrast_threshold {
+        return true;
+    }
+    match (a.depth.is_finite(), b.depth.is_finite()) {
+        (true, true) => {
+            (a.depth - b.depth).abs() > EDGE_DEPTH_RATIO * a.depth.min(b.depth)
+                || a.normal.dot(b.normal) < EDGE_NORMAL_COS
         }
+        (hit_a, hit_b) => hit_a != hit_b,
     }
-    final_color.to_u32_gamma()
 }
 
-fn render_frame(
-    buffer: &mut [u32],
+fn render_adaptive(
+    buffer: &mut [Vec3],
+    width: usize,
+    height: usize,
     camera: &Camera,
-    objects: &[Object],
-    bvh: &Bvh,
-    light: &PointLight,
+    scene: &Scene,
+    settings: &RenderSettings,
 ) {
-    let aspect_ratio = BUFFER_WIDTH as f32 / BUFFER_HEIGHT as f32;
-    let fov_scale = (90.0f32.to_radians() / 2.0).tan();
-    let (cam_forward, cam_right, cam_up) = camera.get_vectors();
+    let primary_ray = ray_generator(camera, width, height);
+
+    let samples: Vec<EdgeSample> = (0..width * height)
+        .into_par_iter()
+        .map(|idx| {
+            let mut rng = Rng::new(idx as u64);
+            let ray = primary_ray((idx % width) as f32 + 0.5, (idx / width) as f32 + 0.5);
+            let hit = scene.bvh.intersect(&ray, &scene.objects);
+            let (normal, depth) = match &hit {
+                Some(hit) => (hit.normal, (hit.point - ray.origin).len()),
+                None => (Vec3::new(0.0, 0.0, 0.0), f32::INFINITY),
+            };
+            EdgeSample {
+                color: settings
+                    .integrator
+                    .shade(&ray, hit, scene, settings, &mut rng),
+                normal,
+                depth,
+            }
+        })
+        .collect();
 
     buffer
-        .par_chunks_mut(BUFFER_WIDTH)
+        .par_chunks_mut(width)
         .enumerate()
         .for_each(|(j, row)| {
             for (i, pixel) in row.iter_mut().enumerate() {
-                let ndc_x =
-                    (2.0 * (i as f32 + 0.5) / BUFFER_WIDTH as f32 - 1.0) * aspect_ratio * fov_scale;
-                let ndc_y = (1.0 - 2.0 * (j as f32 + 0.5) / BUFFER_HEIGHT as f32) * fov_scale;
-                let direction = (cam_forward + cam_right * ndc_x + cam_up * ndc_y).normalize();
+                let center = &samples[j * width + i];
+                let neighbours = [
+                    (i > 0).then(|| j * width + i - 1),
+                    (i + 1 < width).then(|| j * width + i + 1),
+                    (j > 0).then(|| (j - 1) * width + i),
+                    (j + 1 < height).then(|| (j + 1) * width + i),
+                ];
+                let refine = neighbours
+                    .into_iter()
+                    .flatten()
+                    .any(|n| is_edge(center, &samples[n], settings.contrast_threshold));
+                if !refine {
+                    *pixel = center.color;
+                    continue;
+                }
 
-                let ray = Ray::new(camera.pos, direction);
-                *pixel = compute_pixel_color(ray, objects, bvh, light);
+                let perceived = |c: Vec3| linear_to_srgb(c.luminance());
+                let mut sum = center.color;
+                let mut count = 1;
+                let (mut mean, mut m2) = (perceived(center.color), 0.0);
+                for sample in 1..=settings.adaptive_samples {
+                    let stream = (MAX_ACCUMULATED_SAMPLES + sample) as u64;
+                    let mut rng = Rng::new((stream << 32) | (j * width + i) as u64);
+                    let ray = primary_ray(i as f32 + rng.next_f32(), j as f32 + rng.next_f32());
+                    let color = settings
+                        .integrator
+                        .radiance(&ray, scene, settings, &mut rng);
+                    sum = sum + color;
+                    count += 1;
+
+                    let delta = perceived(color) - mean;
+                    mean += delta / count as f32;
+                    m2 += delta * (perceived(color) - mean);
+                    let error = (m2 / (count * (count - 1)) as f32).sqrt();
+                    if count >= ADAPTIVE_MIN_SAMPLES && error <= 0.5 * settings.contrast_threshold {
+                        break;
+                    }
+                }
+                *pixel = sum * (1.0 / count as f32);
             }
         });
 }
 
-fn init_scene() -> (Vec<Object>, Bvh, PointLight) {
+fn init_scene(bvh_config: &BvhConfig) -> Result<Scene, String> {
     let light = PointLight {
         position: Vec3::new(2.0, 5.0, 3.0),
         color: Vec3::new(1.0, 1.0, 0.9),
         intensity: 50.0,
     };
+    let bulb_radius = 0.2;
 
     let mat_mirror = Material {
         albedo: Vec3::new(0.9, 0.9, 0.9),
@@ -129,6 +236,10 @@ fn init_scene() -> (Vec<Object>, Bvh, PointLight) {
         specular: 0.95,
         shininess: 1000.0,
         checkered: false,
+        ior: 1.0,
+        transmission: 0.0,
+        albedo_texture: None,
+        shading: Shading::Phong,
     };
     let mat_gold = Material {
         albedo: Vec3::new(0.8, 0.6, 0.2),
@@ -136,6 +247,10 @@ fn init_scene() -> (Vec<Object>, Bvh, PointLight) {
         specular: 0.6,
         shininess: 64.0,
         checkered: false,
+        ior: 1.0,
+        transmission: 0.0,
+        albedo_texture: None,
+        shading: material::conductor("gold", 0.2).expect("gold is a conductor preset"),
     };
     let mat_checker = Material {
         albedo: Vec3::new(0.9, 0.9, 0.9),
@@ -143,19 +258,27 @@ fn init_scene() -> (Vec<Object>, Bvh, PointLight) {
         specular: 0.1,
         shininess: 32.0,
         checkered: true,
+        ior: 1.0,
+        transmission: 0.0,
+        albedo_texture: None,
+        shading: Shading::Phong,
     };
     let mat_bulb = Material {
         albedo: Vec3::new(0.0, 0.0, 0.0),
-        emission: light.color * light.intensity,
+        emission: light.color * (light.intensity / (bulb_radius * bulb_radius)),
         specular: 0.0,
         shininess: 0.0,
         checkered: false,
+        ior: 1.0,
+        transmission: 0.0,
+        albedo_texture: None,
+        shading: Shading::Phong,
     };
 
     let mut objects = vec![
         Object::Sphere(Sphere {
             center: light.position,
-            radius: 0.2,
+            radius: bulb_radius,
             mat: mat_bulb,
         }),
         Object::Sphere(Sphere {
@@ -167,33 +290,113 @@ fn init_scene() -> (Vec<Object>, Bvh, PointLight) {
             v0: Vec3::new(-20.0, 0.0, -20.0),
             v1: Vec3::new(-20.0, 0.0, 20.0),
             v2: Vec3::new(20.0, 0.0, 20.0),
+            uvs: None,
+            normals: None,
             mat: mat_checker,
         }),
         Object::Triangle(Triangle {
             v0: Vec3::new(-20.0, 0.0, -20.0),
             v1: Vec3::new(20.0, 0.0, 20.0),
             v2: Vec3::new(20.0, 0.0, -20.0),
+            uvs: None,
+            normals: None,
             mat: mat_checker,
         }),
     ];
 
     println!("Loading OBJ model...");
-    let mesh_objects = load_obj(
-        "C:/Users/user/Desktop/rust/raytracer/assets/suzane.obj",
+    let mut textures = Vec::new();
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/suzane.obj");
+    let mesh = load_obj(
+        path,
         Vec3::new(-1.5, 2.0, 0.0),
         1.0,
         mat_gold,
-    );
-    objects.extend(mesh_objects);
+        &mut textures,
+    )
+    .map_err(|e| format!("{}: {}", path, e))?;
+    objects.push(Object::Mesh(mesh));
 
-    println!("Building BVH for {} objects...", objects.len());
-    let bvh = Bvh::build(&objects);
+    let scene = Scene::new(objects, Vec::new(), textures, bvh_config);
     println!("Scene initialized.");
+    Ok(scene)
+}
+
+fn run_headless(
+    options: &Options,
+    scene: &mut Scene,
+    mut camera: Camera,
+    settings: &RenderSettings,
+) {
+    let mut sample_buffer = vec![Vec3::new(0.0, 0.0, 0.0); options.width * options.height];
+    let mut hdr_buffer = sample_buffer.clone();
+    let mut accumulator = Accumulator::new(sample_buffer.len());
 
-    (objects, bvh, light)
+    for frame in 0..options.frames {
+        let start = Instant::now();
+        scene.animate(frame);
+        scene.update();
+        accumulator.reset();
+        for sample in 0..settings.samples_per_pixel.max(1) {
+            render_frame(
+                &mut sample_buffer,
+                options.width,
+                options.height,
+                &camera,
+                scene,
+                settings,
+                sample,
+            );
+            accumulator.add(&sample_buffer);
+        }
+        accumulator.resolve(&mut hdr_buffer);
+        let path = options.frame_path(frame);
+        if let Err(e) = write_image(
+            &path,
+            options.width,
+            options.height,
+            &hdr_buffer,
+            &settings.tone_mapping,
+        ) {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+            process::exit(1);
+        }
+        println!("Rendered {} in {:.2?}", path.display(), start.elapsed());
+
+        for &aov in &options.aovs {
+            render_aov(
+                &mut sample_buffer,
+                options.width,
+                options.height,
+                &camera,
+                scene,
+                aov,
+            );
+            let path = options.aov_path(frame, aov);
+            if !path
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("pfm"))
+            {
+                aov.normalize(&mut sample_buffer);
+            }
+            if let Err(e) = write_image(
+                &path,
+                options.width,
+                options.height,
+                &sample_buffer,
+                &aov.tone_mapping(),
+            ) {
+                eprintln!("Failed to write {}: {}", path.display(), e);
+                process::exit(1);
+            }
+            println!("Wrote {}", path.display());
+        }
+        camera.yaw += options.yaw_step;
+    }
 }
 
-fn main() {
+fn run_viewer(scene: &mut Scene, mut camera: Camera, settings: &RenderSettings) {
+    let mut settings = *settings;
     let mut window = Window::new(
         "Raytracer Rust",
         WINDOW_WIDTH,
@@ -210,19 +413,144 @@ fn main() {
     .unwrap();
 
     window.set_target_fps(60);
+    let mut sample_buffer = vec![Vec3::new(0.0, 0.0, 0.0); BUFFER_WIDTH * BUFFER_HEIGHT];
+    let mut hdr_buffer = sample_buffer.clone();
+    let mut accumulator = Accumulator::new(sample_buffer.len());
     let mut buffer: Vec<u32> = vec![0; BUFFER_WIDTH * BUFFER_HEIGHT];
 
-    let (objects, bvh, light) = init_scene();
-
-    let start_mouse = window.get_mouse_pos(MouseMode::Pass).unwrap_or((0.0, 0.0));
-    let mut camera = Camera::new(Vec3::new(0.0, 2.0, 5.0), start_mouse);
-    camera.pitch = -20.0;
+    camera.last_mouse_pos = window.get_mouse_pos(MouseMode::Pass).unwrap_or((0.0, 0.0));
 
+    let mut tone_mapping = settings.tone_mapping;
+    let mut aov: Option<Aov> = None;
+    let mut frame = 0;
     while window.is_open() && !window.is_key_down(Key::Escape) {
         camera.update(&window);
-        render_frame(&mut buffer, &camera, &objects, &bvh, &light);
+        if window.is_key_pressed(Key::I, KeyRepeat::No) {
+            settings.integrator = integrator::next(settings.integrator);
+            accumulator.reset();
+            println!("Integrator: {}", settings.integrator.name());
+        }
+        if window.is_key_pressed(Key::V, KeyRepeat::No) {
+            aov = match aov {
+                Some(current) => current.next(),
+                None => Some(Aov::ALL[0]),
+            };
+            accumulator.reset();
+            println!("Display: {}", aov.map_or("shaded", Aov::name));
+        }
+        if window.is_key_pressed(Key::T, KeyRepeat::No) {
+            tone_mapping.operator = tone_mapping.operator.next();
+            println!("Tone mapping: {:?}", tone_mapping.operator);
+        }
+        if window.is_key_pressed(Key::X, KeyRepeat::No) {
+            tone_mapping.auto_exposure = !tone_mapping.auto_exposure;
+            println!("Auto exposure: {}", tone_mapping.auto_exposure);
+        }
+        if window.is_key_pressed(Key::Equal, KeyRepeat::Yes) {
+            tone_mapping.exposure += 0.25;
+            println!("Exposure: {:+.2} EV", tone_mapping.exposure);
+        }
+        if window.is_key_pressed(Key::Minus, KeyRepeat::Yes) {
+            tone_mapping.exposure -= 0.25;
+            println!("Exposure: {:+.2} EV", tone_mapping.exposure);
+        }
+        scene.animate(frame);
+        if scene.update() {
+            accumulator.reset();
+        }
+        frame += 1;
+        if let Some(aov) = aov {
+            render_aov(
+                &mut hdr_buffer,
+                BUFFER_WIDTH,
+                BUFFER_HEIGHT,
+                &camera,
+                scene,
+                aov,
+            );
+            aov.normalize(&mut hdr_buffer);
+            let display = aov.tone_mapping();
+            buffer
+                .par_iter_mut()
+                .zip(hdr_buffer.par_iter())
+                .for_each(|(pixel, color)| *pixel = display.encode(*color, 1.0));
+        } else {
+            accumulator.track(&camera);
+            if accumulator.samples < MAX_ACCUMULATED_SAMPLES {
+                render_frame(
+                    &mut sample_buffer,
+                    BUFFER_WIDTH,
+                    BUFFER_HEIGHT,
+                    &camera,
+                    scene,
+                    &settings,
+                    accumulator.samples,
+                );
+                accumulator.add(&sample_buffer);
+                accumulator.resolve(&mut hdr_buffer);
+            }
+            let scale = tone_mapping.exposure_scale(&hdr_buffer);
+            buffer
+                .par_iter_mut()
+                .zip(hdr_buffer.par_iter())
+                .for_each(|(pixel, color)| *pixel = tone_mapping.encode(*color, scale));
+        }
         window
             .update_with_buffer(&buffer, BUFFER_WIDTH, BUFFER_HEIGHT)
             .unwrap();
     }
 }
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let (mut scene, mut camera, mut settings) = match &options.scene {
+        Some(path) => match load_scene(path, &options.bvh) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                eprintln!("Failed to load scene: {}", e);
+                process::exit(1);
+            }
+        },
+        None => {
+            let mut camera = Camera::new(Vec3::new(0.0, 2.0, 5.0), (0.0, 0.0));
+            camera.pitch = -20.0;
+            match init_scene(&options.bvh) {
+                Ok(scene) => (scene, camera, RenderSettings::default()),
+                Err(e) => {
+                    eprintln!("Failed to load scene: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+    };
+    options.apply_overrides(&mut camera, &mut settings);
+    if let Some(path) = &options.environment {
+        match Texture::load(path, WrapMode::Repeat, FilterMode::Bilinear) {
+            Ok(texture) => {
+                scene.environment = Environment::new(
+                    EnvironmentMap::Equirectangular(texture),
+                    Vec3::new(0.0, options.env_rotation, 0.0),
+                    options.env_intensity,
+                    true,
+                );
+            }
+            Err(e) => {
+                eprintln!("Failed to load environment map: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    if options.headless {
+        run_headless(&options, &mut scene, camera, &settings);
+    } else {
+        run_viewer(&mut scene, camera, &settings);
+    }
+}
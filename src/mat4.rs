@@ -0,0 +1,133 @@
+use crate::vec3::Vec3;
+use std::ops::Mul;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Mat4 {
+    pub const fn identity() -> Self {
+        Self {
+            m: [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+    pub fn translation(t: Vec3) -> Self {
+        let mut r = Self::identity();
+        r.m[0][3] = t.x;
+        r.m[1][3] = t.y;
+        r.m[2][3] = t.z;
+        r
+    }
+    pub fn scale(s: Vec3) -> Self {
+        let mut r = Self::identity();
+        r.m[0][0] = s.x;
+        r.m[1][1] = s.y;
+        r.m[2][2] = s.z;
+        r
+    }
+    pub fn rotation_x(degrees: f32) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let mut r = Self::identity();
+        r.m[1][1] = cos;
+        r.m[1][2] = -sin;
+        r.m[2][1] = sin;
+        r.m[2][2] = cos;
+        r
+    }
+    pub fn rotation_y(degrees: f32) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let mut r = Self::identity();
+        r.m[0][0] = cos;
+        r.m[0][2] = sin;
+        r.m[2][0] = -sin;
+        r.m[2][2] = cos;
+        r
+    }
+    pub fn rotation_z(degrees: f32) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let mut r = Self::identity();
+        r.m[0][0] = cos;
+        r.m[0][1] = -sin;
+        r.m[1][0] = sin;
+        r.m[1][1] = cos;
+        r
+    }
+    pub fn from_trs(translation: Vec3, rotation: Vec3, scale: Vec3) -> Self {
+        Self::translation(translation)
+            * Self::rotation_z(rotation.z)
+            * Self::rotation_y(rotation.y)
+            * Self::rotation_x(rotation.x)
+            * Self::scale(scale)
+    }
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3],
+            m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3],
+            m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3],
+        )
+    }
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+    pub fn transform_normal_transposed(&self, n: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * n.x + m[1][0] * n.y + m[2][0] * n.z,
+            m[0][1] * n.x + m[1][1] * n.y + m[2][1] * n.z,
+            m[0][2] * n.x + m[1][2] * n.y + m[2][2] * n.z,
+        )
+    }
+    pub fn affine_inverse(&self) -> Option<Self> {
+        let m = &self.m;
+        let c00 = m[1][1] * m[2][2] - m[1][2] * m[2][1];
+        let c01 = m[1][2] * m[2][0] - m[1][0] * m[2][2];
+        let c02 = m[1][0] * m[2][1] - m[1][1] * m[2][0];
+        let det = m[0][0] * c00 + m[0][1] * c01 + m[0][2] * c02;
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let mut r = Self::identity();
+        r.m[0][0] = c00 * inv_det;
+        r.m[0][1] = (m[0][2] * m[2][1] - m[0][1] * m[2][2]) * inv_det;
+        r.m[0][2] = (m[0][1] * m[1][2] - m[0][2] * m[1][1]) * inv_det;
+        r.m[1][0] = c01 * inv_det;
+        r.m[1][1] = (m[0][0] * m[2][2] - m[0][2] * m[2][0]) * inv_det;
+        r.m[1][2] = (m[0][2] * m[1][0] - m[0][0] * m[1][2]) * inv_det;
+        r.m[2][0] = c02 * inv_det;
+        r.m[2][1] = (m[0][1] * m[2][0] - m[0][0] * m[2][1]) * inv_det;
+        r.m[2][2] = (m[0][0] * m[1][1] - m[0][1] * m[1][0]) * inv_det;
+
+        let t = r.transform_vector(Vec3::new(m[0][3], m[1][3], m[2][3]));
+        r.m[0][3] = -t.x;
+        r.m[1][3] = -t.y;
+        r.m[2][3] = -t.z;
+        Some(r)
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+    fn mul(self, o: Mat4) -> Mat4 {
+        let mut r = Mat4 { m: [[0.0; 4]; 4] };
+        for i in 0..4 {
+            for j in 0..4 {
+                r.m[i][j] = (0..4).map(|k| self.m[i][k] * o.m[k][j]).sum();
+            }
+        }
+        r
+    }
+}
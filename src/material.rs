@@ -1,5 +1,47 @@
 use crate::vec3::Vec3;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shading {
+    Phong,
+    MetallicRoughness { metallic: f32, roughness: f32 },
+    Conductor { eta: Vec3, k: Vec3, roughness: f32 },
+}
+
+pub const CONDUCTORS: [(&str, Vec3, Vec3); 5] = [
+    (
+        "gold",
+        Vec3::new(0.143, 0.374, 1.442),
+        Vec3::new(3.983, 2.386, 1.603),
+    ),
+    (
+        "copper",
+        Vec3::new(0.200, 0.924, 1.102),
+        Vec3::new(3.912, 2.452, 2.142),
+    ),
+    (
+        "silver",
+        Vec3::new(0.155, 0.117, 0.138),
+        Vec3::new(4.828, 3.122, 2.147),
+    ),
+    (
+        "aluminium",
+        Vec3::new(1.657, 0.880, 0.521),
+        Vec3::new(9.224, 6.270, 4.837),
+    ),
+    (
+        "iron",
+        Vec3::new(2.911, 2.950, 2.585),
+        Vec3::new(3.089, 2.932, 2.767),
+    ),
+];
+
+pub fn conductor(name: &str, roughness: f32) -> Option<Shading> {
+    CONDUCTORS
+        .iter()
+        .find(|(preset, _, _)| *preset == name)
+        .map(|&(_, eta, k)| Shading::Conductor { eta, k, roughness })
+}
+
 #[derive(Clone, Copy)]
 pub struct Material {
     pub albedo: Vec3,
@@ -7,4 +49,8 @@ pub struct Material {
     pub specular: f32,
     pub shininess: f32,
     pub checkered: bool,
+    pub ior: f32,
+    pub transmission: f32,
+    pub albedo_texture: Option<usize>,
+    pub shading: Shading,
 }
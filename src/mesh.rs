@@ -0,0 +1,319 @@
+use crate::aabb::Aabb;
+use crate::constants::CREASE_ANGLE_COS;
+use crate::light::{Light, MeshLight, TriangleLight};
+use crate::material::{Material, Shading};
+use crate::objects::{Hit, intersect_triangle, triangle_hit};
+use crate::ray::Ray;
+use crate::texture::{FilterMode, Texture, WrapMode};
+use crate::vec3::Vec3;
+use std::collections::HashMap;
+use std::path::Path;
+
+pub struct Mesh {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f32, f32)>,
+    pub indices: Vec<[u32; 3]>,
+    pub material_ids: Vec<u32>,
+    pub materials: Vec<Material>,
+}
+
+impl Mesh {
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len()
+    }
+    fn vertices(&self, tri: usize) -> [Vec3; 3] {
+        self.indices[tri].map(|i| self.positions[i as usize])
+    }
+    pub fn material(&self, tri: usize) -> &Material {
+        &self.materials[self.material_ids[tri] as usize]
+    }
+    pub fn triangle_aabb(&self, tri: usize) -> Aabb {
+        let mut aabb = Aabb::empty();
+        for v in self.vertices(tri) {
+            aabb.grow(v);
+        }
+        aabb
+    }
+    pub fn hit_distance(&self, tri: usize, ray: &Ray) -> Option<f32> {
+        let [v0, v1, v2] = self.vertices(tri);
+        intersect_triangle(v0, v1, v2, ray).map(|(t, _, _)| t)
+    }
+    pub fn intersect(&self, tri: usize, ray: &Ray) -> Option<Hit> {
+        let vertices = self.vertices(tri);
+        let barycentric = intersect_triangle(vertices[0], vertices[1], vertices[2], ray)?;
+        let idx = self.indices[tri];
+        let normals = (!self.normals.is_empty()).then(|| idx.map(|i| self.normals[i as usize]));
+        let uvs = (!self.uvs.is_empty()).then(|| idx.map(|i| self.uvs[i as usize]));
+        Some(triangle_hit(
+            barycentric,
+            vertices,
+            normals,
+            uvs,
+            *self.material(tri),
+        ))
+    }
+    pub fn emitters(&self) -> Vec<Light> {
+        let triangles: Vec<TriangleLight> = (0..self.triangle_count())
+            .filter_map(|tri| {
+                let emission = self.material(tri).emission;
+                if emission.max_component() <= 0.0 {
+                    return None;
+                }
+                let [v0, v1, v2] = self.vertices(tri);
+                Some(TriangleLight {
+                    v0,
+                    v1,
+                    v2,
+                    emission,
+                })
+            })
+            .collect();
+        if triangles.is_empty() {
+            return Vec::new();
+        }
+        vec![Light::Mesh(MeshLight::new(triangles))]
+    }
+}
+
+fn convert_mtl_material(
+    mtl: &tobj::Material,
+    fallback: Material,
+    base_dir: &Path,
+    textures: &mut Vec<Texture>,
+    texture_ids: &mut HashMap<String, usize>,
+) -> Result<Material, String> {
+    let mut mat = fallback;
+    mat.albedo_texture = None;
+
+    if let Some([r, g, b]) = mtl.diffuse {
+        mat.albedo = Vec3::new(r, g, b);
+    }
+    if let Some([r, g, b]) = mtl.specular {
+        mat.specular = ((r + g + b) / 3.0).clamp(0.0, 1.0);
+    }
+    if let Some(ns) = mtl.shininess {
+        mat.shininess = ns;
+    }
+    if let Some([r, g, b]) = mtl.emissive {
+        mat.emission = Vec3::new(r, g, b);
+    }
+    if let Some(ni) = mtl.optical_density {
+        mat.ior = ni;
+    }
+    if let Some(d) = mtl.dissolve {
+        mat.transmission = (1.0 - d).clamp(0.0, 1.0);
+    }
+    let pbr_param = |key: &str| {
+        mtl.unknown_param
+            .get(key)
+            .and_then(|v| v.trim().parse().ok())
+    };
+    let (metallic, roughness): (Option<f32>, Option<f32>) = (pbr_param("Pm"), pbr_param("Pr"));
+    if metallic.is_some() || roughness.is_some() {
+        mat.shading = Shading::MetallicRoughness {
+            metallic: metallic.unwrap_or(0.0).clamp(0.0, 1.0),
+            roughness: roughness.unwrap_or(0.5).clamp(0.0, 1.0),
+        };
+    }
+
+    if let Some(map_kd) = &mtl.diffuse_texture {
+        if mtl.diffuse.is_none() {
+            mat.albedo = Vec3::new(1.0, 1.0, 1.0);
+        }
+        if let Some(&id) = texture_ids.get(map_kd) {
+            mat.albedo_texture = Some(id);
+        } else {
+            let texture = Texture::load(
+                &base_dir.join(map_kd),
+                WrapMode::Repeat,
+                FilterMode::Bilinear,
+            )
+            .map_err(|e| format!("failed to load texture {}: {}", map_kd, e))?;
+            texture_ids.insert(map_kd.clone(), textures.len());
+            mat.albedo_texture = Some(textures.len());
+            textures.push(texture);
+        }
+    }
+    Ok(mat)
+}
+
+struct ShadedVertices {
+    sources: Vec<usize>,
+    normals: Vec<Vec3>,
+    indices: Vec<u32>,
+}
+
+fn vertex_normals(mesh: &tobj::Mesh) -> ShadedVertices {
+    let position = |idx: usize| {
+        Vec3::new(
+            mesh.positions[3 * idx],
+            mesh.positions[3 * idx + 1],
+            mesh.positions[3 * idx + 2],
+        )
+    };
+    let vertex_count = mesh.positions.len() / 3;
+
+    if mesh.normals.len() == mesh.positions.len() {
+        return ShadedVertices {
+            sources: (0..vertex_count).collect(),
+            normals: (0..vertex_count)
+                .map(|idx| {
+                    Vec3::new(
+                        mesh.normals[3 * idx],
+                        mesh.normals[3 * idx + 1],
+                        mesh.normals[3 * idx + 2],
+                    )
+                    .normalize()
+                })
+                .collect(),
+            indices: mesh.indices.clone(),
+        };
+    }
+
+    let mut welded = HashMap::new();
+    let slots: Vec<usize> = (0..vertex_count)
+        .map(|idx| {
+            let p = position(idx);
+            let key = (p.x.to_bits(), p.y.to_bits(), p.z.to_bits());
+            let next = welded.len();
+            *welded.entry(key).or_insert(next)
+        })
+        .collect();
+
+    let face_normals: Vec<Vec3> = mesh
+        .indices
+        .chunks_exact(3)
+        .map(|face| {
+            let [p0, p1, p2] = [face[0], face[1], face[2]].map(|i| position(i as usize));
+            (p1 - p0).cross(p2 - p0)
+        })
+        .collect();
+    let mut adjacent = vec![Vec::new(); welded.len()];
+    for (corner, &idx) in mesh.indices.iter().enumerate() {
+        adjacent[slots[idx as usize]].push(corner / 3);
+    }
+
+    let mut shaded = ShadedVertices {
+        sources: Vec::new(),
+        normals: Vec::new(),
+        indices: Vec::with_capacity(mesh.indices.len()),
+    };
+    let mut split = HashMap::new();
+    for (corner, &idx) in mesh.indices.iter().enumerate() {
+        let face_normal = face_normals[corner / 3].normalize();
+        let normal = adjacent[slots[idx as usize]]
+            .iter()
+            .map(|&face| face_normals[face])
+            .filter(|n| n.normalize().dot(face_normal) >= CREASE_ANGLE_COS)
+            .fold(Vec3::new(0.0, 0.0, 0.0), |sum, n| sum + n)
+            .normalize();
+        let key = (
+            idx,
+            normal.x.to_bits(),
+            normal.y.to_bits(),
+            normal.z.to_bits(),
+        );
+        let vertex = *split.entry(key).or_insert_with(|| {
+            shaded.sources.push(idx as usize);
+            shaded.normals.push(normal);
+            shaded.sources.len() as u32 - 1
+        });
+        shaded.indices.push(vertex);
+    }
+    shaded
+}
+
+pub fn load_obj(
+    path: &str,
+    translation: Vec3,
+    scale: f32,
+    mat: Material,
+    textures: &mut Vec<Texture>,
+) -> Result<Mesh, String> {
+    let path_obj = Path::new(path);
+    let (models, materials) = tobj::load_obj(
+        path_obj,
+        &tobj::LoadOptions {
+            single_index: true,
+            triangulate: true,
+            ..Default::default()
+        },
+    )
+    .map_err(|e| e.to_string())?;
+
+    let base_dir = path_obj.parent().unwrap_or(Path::new(""));
+    let mut texture_ids = HashMap::new();
+    let mut materials: Vec<Material> = match materials {
+        Ok(materials) => materials
+            .iter()
+            .map(|m| convert_mtl_material(m, mat, base_dir, textures, &mut texture_ids))
+            .collect::<Result<_, _>>()?,
+        Err(e) => {
+            eprintln!("Failed to load materials for {}: {}", path, e);
+            Vec::new()
+        }
+    };
+    let fallback_id = materials.len() as u32;
+    materials.push(mat);
+
+    let mut result = Mesh {
+        positions: Vec::new(),
+        normals: Vec::new(),
+        uvs: Vec::new(),
+        indices: Vec::new(),
+        material_ids: Vec::new(),
+        materials,
+    };
+    let has_uvs = models.iter().any(|m| !m.mesh.texcoords.is_empty());
+    let normal_sign = scale.signum();
+
+    for model in models {
+        let mesh = model.mesh;
+        let material_id = mesh
+            .material_id
+            .filter(|&id| id < fallback_id as usize)
+            .map_or(fallback_id, |id| id as u32);
+        let offset = result.positions.len() as u32;
+        let shaded = vertex_normals(&mesh);
+
+        result.positions.extend(shaded.sources.iter().map(|&i| {
+            Vec3::new(
+                mesh.positions[3 * i],
+                mesh.positions[3 * i + 1],
+                mesh.positions[3 * i + 2],
+            ) * scale
+                + translation
+        }));
+        result
+            .normals
+            .extend(shaded.normals.iter().map(|&n| n * normal_sign));
+        if has_uvs {
+            if mesh.texcoords.is_empty() {
+                result
+                    .uvs
+                    .extend(std::iter::repeat_n((0.0, 0.0), shaded.sources.len()));
+            } else {
+                result.uvs.extend(
+                    shaded
+                        .sources
+                        .iter()
+                        .map(|&i| (mesh.texcoords[2 * i], mesh.texcoords[2 * i + 1])),
+                );
+            }
+        }
+        for face in shaded.indices.chunks_exact(3) {
+            result
+                .indices
+                .push([face[0] + offset, face[1] + offset, face[2] + offset]);
+            result.material_ids.push(material_id);
+        }
+    }
+
+    println!(
+        "Loaded {} triangles from {:?}",
+        result.triangle_count(),
+        path
+    );
+    Ok(result)
+}
@@ -1,20 +1,31 @@
 use crate::aabb::Aabb;
+use crate::instance::Instance;
+use crate::light::{Light, SphereLight, TriangleLight};
 use crate::material::Material;
+use crate::mesh::Mesh;
 use crate::ray::Ray;
 use crate::vec3::Vec3;
-use std::path::Path;
+use std::f32::consts::PI;
 
 #[derive(Clone)]
 pub struct Intersection {
     pub point: Vec3,
     pub normal: Vec3,
+    pub front_face: bool,
+    pub uv: (f32, f32),
+    pub barycentric: Vec3,
+    pub object: [u32; 2],
     pub material: Material,
 }
 
-pub struct PointLight {
-    pub position: Vec3,
-    pub color: Vec3,
-    pub intensity: f32,
+pub struct Hit {
+    pub t: f32,
+    pub normal: Vec3,
+    pub geometric_normal: Vec3,
+    pub uv: (f32, f32),
+    pub barycentric: Vec3,
+    pub object: [u32; 2],
+    pub material: Material,
 }
 
 pub struct Sphere {
@@ -31,7 +42,7 @@ impl Sphere {
             max: self.center + r_vec,
         }
     }
-    pub fn intersect(&self, ray: &Ray) -> Option<(f32, Vec3, Material)> {
+    pub fn hit_distance(&self, ray: &Ray) -> Option<f32> {
         let oc = ray.origin - self.center;
         let a = ray.direction.dot(ray.direction);
         let b = 2.0 * oc.dot(ray.direction);
@@ -40,13 +51,94 @@ impl Sphere {
         if discriminant < 0.0 {
             return None;
         }
-        let t = (-b - discriminant.sqrt()) / (2.0 * a);
-        if t < 0.001 {
+        let sqrt_d = discriminant.sqrt();
+        let mut t = (-b - sqrt_d) / (2.0 * a);
+        if t <= ray.t_min {
+            t = (-b + sqrt_d) / (2.0 * a);
+        }
+        if t <= ray.t_min || t >= ray.t_max {
             return None;
         }
+        Some(t)
+    }
+    pub fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        let t = self.hit_distance(ray)?;
         let hit_point = ray.origin + t * ray.direction;
         let normal = (hit_point - self.center).normalize();
-        Some((t, normal, self.mat))
+        let uv = (
+            0.5 + normal.z.atan2(normal.x) / (2.0 * PI),
+            0.5 + normal.y.clamp(-1.0, 1.0).asin() / PI,
+        );
+        Some(Hit {
+            t,
+            normal,
+            geometric_normal: normal,
+            uv,
+            barycentric: Vec3::new(0.0, 0.0, 0.0),
+            object: [0, 0],
+            material: self.mat,
+        })
+    }
+}
+
+pub fn intersect_triangle(v0: Vec3, v1: Vec3, v2: Vec3, ray: &Ray) -> Option<(f32, f32, f32)> {
+    let epsilon = 1e-6;
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+    let h = ray.direction.cross(edge2);
+    let a = edge1.dot(h);
+    if a.abs() < epsilon {
+        return None;
+    }
+    let f = 1.0 / a;
+    let s = ray.origin - v0;
+    let u = f * s.dot(h);
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(edge1);
+    let v = f * ray.direction.dot(q);
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = f * edge2.dot(q);
+    if t > ray.t_min && t < ray.t_max {
+        return Some((t, u, v));
+    }
+    None
+}
+
+pub fn triangle_hit(
+    (t, u, v): (f32, f32, f32),
+    [v0, v1, v2]: [Vec3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f32, f32); 3]>,
+    material: Material,
+) -> Hit {
+    let w = 1.0 - u - v;
+    let geometric_normal = (v1 - v0).cross(v2 - v0).normalize();
+    let normal = match normals {
+        Some([n0, n1, n2]) => {
+            let n = (n0 * w + n1 * u + n2 * v).normalize();
+            if n.dot(geometric_normal) < 0.0 { -n } else { n }
+        }
+        None => geometric_normal,
+    };
+    let uv = match uvs {
+        Some([uv0, uv1, uv2]) => (
+            w * uv0.0 + u * uv1.0 + v * uv2.0,
+            w * uv0.1 + u * uv1.1 + v * uv2.1,
+        ),
+        None => (u, v),
+    };
+    Hit {
+        t,
+        normal,
+        geometric_normal,
+        uv,
+        barycentric: Vec3::new(w, u, v),
+        object: [0, 0],
+        material,
     }
 }
 
@@ -54,6 +146,8 @@ pub struct Triangle {
     pub v0: Vec3,
     pub v1: Vec3,
     pub v2: Vec3,
+    pub uvs: Option<[(f32, f32); 3]>,
+    pub normals: Option<[Vec3; 3]>,
     pub mat: Material,
 }
 
@@ -65,104 +159,81 @@ impl Triangle {
         aabb.grow(self.v2);
         aabb
     }
-    pub fn intersect(&self, ray: &Ray) -> Option<(f32, Vec3, Material)> {
-        let epsilon = 1e-6;
-        let edge1 = self.v1 - self.v0;
-        let edge2 = self.v2 - self.v0;
-        let h = ray.direction.cross(edge2);
-        let a = edge1.dot(h);
-        if a.abs() < epsilon {
-            return None;
-        }
-        let f = 1.0 / a;
-        let s = ray.origin - self.v0;
-        let u = f * s.dot(h);
-        if !(0.0..=1.0).contains(&u) {
-            return None;
-        }
-        let q = s.cross(edge1);
-        let v = f * ray.direction.dot(q);
-        if v < 0.0 || u + v > 1.0 {
-            return None;
-        }
-        let t = f * edge2.dot(q);
-        if t > epsilon {
-            let mut normal = edge1.cross(edge2).normalize();
-            if normal.dot(ray.direction) > 0.0 {
-                normal = -normal;
-            }
-            return Some((t, normal, self.mat));
-        }
-        None
+    pub fn hit_distance(&self, ray: &Ray) -> Option<f32> {
+        intersect_triangle(self.v0, self.v1, self.v2, ray).map(|(t, _, _)| t)
+    }
+    pub fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        let barycentric = intersect_triangle(self.v0, self.v1, self.v2, ray)?;
+        Some(triangle_hit(
+            barycentric,
+            [self.v0, self.v1, self.v2],
+            self.normals,
+            self.uvs,
+            self.mat,
+        ))
     }
 }
 
 pub enum Object {
     Sphere(Sphere),
     Triangle(Triangle),
+    Mesh(Mesh),
+    Instance(Instance),
 }
 
 impl Object {
-    pub fn aabb(&self) -> Aabb {
+    pub fn primitive_count(&self) -> usize {
+        match self {
+            Object::Mesh(m) => m.triangle_count(),
+            _ => 1,
+        }
+    }
+    pub fn aabb(&self, prim: usize) -> Aabb {
         match self {
             Object::Sphere(s) => s.aabb(),
             Object::Triangle(t) => t.aabb(),
+            Object::Mesh(m) => m.triangle_aabb(prim),
+            Object::Instance(i) => i.aabb(),
         }
     }
-    pub fn intersect(&self, ray: &Ray) -> Option<(f32, Vec3, Material)> {
+    pub fn intersect(&self, prim: usize, ray: &Ray) -> Option<Hit> {
         match self {
             Object::Sphere(s) => s.intersect(ray),
             Object::Triangle(t) => t.intersect(ray),
+            Object::Mesh(m) => m.intersect(prim, ray),
+            Object::Instance(i) => i.intersect(ray),
         }
     }
-}
-
-pub fn load_obj(path: &str, translation: Vec3, scale: f32, mat: Material) -> Vec<Object> {
-    let path_obj = Path::new(path);
-    let (models, _materials) = tobj::load_obj(
-        path_obj,
-        &tobj::LoadOptions {
-            single_index: true,
-            triangulate: true,
-            ..Default::default()
-        },
-    )
-    .expect("Failed to load OBJ file");
-
-    let mut objects = Vec::new();
-
-    for model in models {
-        let mesh = model.mesh;
-
-        for i in (0..mesh.indices.len()).step_by(3) {
-            let idx0 = mesh.indices[i] as usize;
-            let idx1 = mesh.indices[i + 1] as usize;
-            let idx2 = mesh.indices[i + 2] as usize;
-
-            let v0_raw = Vec3::new(
-                mesh.positions[3 * idx0],
-                mesh.positions[3 * idx0 + 1],
-                mesh.positions[3 * idx0 + 2],
-            );
-            let v1_raw = Vec3::new(
-                mesh.positions[3 * idx1],
-                mesh.positions[3 * idx1 + 1],
-                mesh.positions[3 * idx1 + 2],
-            );
-            let v2_raw = Vec3::new(
-                mesh.positions[3 * idx2],
-                mesh.positions[3 * idx2 + 1],
-                mesh.positions[3 * idx2 + 2],
-            );
-
-            let v0 = v0_raw * scale + translation;
-            let v1 = v1_raw * scale + translation;
-            let v2 = v2_raw * scale + translation;
-
-            objects.push(Object::Triangle(Triangle { v0, v1, v2, mat }));
+    pub fn occludes(&self, prim: usize, ray: &Ray) -> bool {
+        let (material, distance) = match self {
+            Object::Instance(i) => return i.occluded(ray),
+            Object::Sphere(s) => (&s.mat, s.hit_distance(ray)),
+            Object::Triangle(t) => (&t.mat, t.hit_distance(ray)),
+            Object::Mesh(m) => (m.material(prim), m.hit_distance(prim, ray)),
+        };
+        material.emission.max_component() <= 0.0 && distance.is_some()
+    }
+    pub fn emitters(&self) -> Vec<Light> {
+        match self {
+            Object::Sphere(s) if s.mat.emission.max_component() > 0.0 => {
+                vec![Light::Sphere(SphereLight {
+                    center: s.center,
+                    radius: s.radius,
+                    emission: s.mat.emission,
+                    geometric: true,
+                })]
+            }
+            Object::Triangle(t) if t.mat.emission.max_component() > 0.0 => {
+                vec![Light::Triangle(TriangleLight {
+                    v0: t.v0,
+                    v1: t.v1,
+                    v2: t.v2,
+                    emission: t.mat.emission,
+                })]
+            }
+            Object::Mesh(m) => m.emitters(),
+            Object::Instance(i) => i.emitters(),
+            _ => Vec::new(),
         }
     }
-
-    println!("Loaded {} triangles from {:?}", objects.len(), path);
-    objects
 }
@@ -0,0 +1,127 @@
+use crate::brdf;
+use crate::constants::{PATH_MAX_BOUNCES, RAY_EPSILON, RUSSIAN_ROULETTE_DEPTH};
+use crate::integrator::Integrator;
+use crate::objects::Intersection;
+use crate::ray::Ray;
+use crate::rng::Rng;
+use crate::scene::{RenderSettings, Scene};
+use crate::surface::Surface;
+use crate::vec3::Vec3;
+
+pub struct PathTracer;
+
+fn direct_light(point: Vec3, surface: &Surface, scene: &Scene, rng: &mut Rng) -> Vec3 {
+    let mut radiance = Vec3::new(0.0, 0.0, 0.0);
+    for light in &scene.lights {
+        let Some(sample) = light.sample(point, rng) else {
+            continue;
+        };
+        if surface.normal.dot(sample.direction) <= 0.0 {
+            continue;
+        }
+        let shadow_ray = Ray::with_interval(
+            point,
+            sample.direction,
+            RAY_EPSILON,
+            sample.distance - RAY_EPSILON,
+        );
+        if scene.bvh.occluded(&shadow_ray, &scene.objects) {
+            continue;
+        }
+        let response = if light.is_geometric() && surface.roughness() == 0.0 {
+            surface.diffuse_response(sample.direction)
+        } else {
+            surface.response(sample.direction)
+        };
+        radiance = radiance + response.mul_vec(sample.radiance);
+    }
+    radiance
+}
+
+fn background(direction: Vec3, scene: &Scene) -> Vec3 {
+    scene
+        .lights
+        .iter()
+        .fold(scene.environment.radiance(direction), |sum, light| {
+            sum + light.ambient(direction)
+        })
+}
+
+fn trace(ray: &Ray, hit: Option<Intersection>, scene: &Scene, rng: &mut Rng) -> Vec3 {
+    let mut ray = *ray;
+    let mut next_hit = hit;
+    let mut radiance = Vec3::new(0.0, 0.0, 0.0);
+    let mut throughput = Vec3::new(1.0, 1.0, 1.0);
+    let mut specular_bounce = true;
+
+    for depth in 0..PATH_MAX_BOUNCES {
+        let Some(hit) = next_hit.take() else {
+            radiance = radiance + throughput.mul_vec(background(ray.direction, scene));
+            break;
+        };
+        if specular_bounce {
+            radiance = radiance + throughput.mul_vec(hit.material.emission);
+        }
+
+        let surface = Surface::new(&ray, &hit, &scene.textures);
+        radiance = radiance + throughput.mul_vec(direct_light(hit.point, &surface, scene, rng));
+
+        let transmitted = match surface.refracted {
+            Some(_) => surface.albedo * ((1.0 - surface.fresnel) * hit.material.transmission),
+            None => Vec3::new(0.0, 0.0, 0.0),
+        };
+        let diffuse_weight = surface.ambient_diffuse.luminance();
+        let reflect_weight = surface.reflectance.luminance();
+        let total = diffuse_weight + reflect_weight + transmitted.luminance();
+        if total <= 0.0 {
+            break;
+        }
+
+        let choice = rng.next_f32() * total;
+        let (direction, weight) = if choice < diffuse_weight {
+            specular_bounce = false;
+            (
+                brdf::sample_cosine_hemisphere(hit.normal, rng),
+                surface.ambient_diffuse,
+            )
+        } else if choice < diffuse_weight + reflect_weight {
+            specular_bounce = surface.roughness() == 0.0;
+            (surface.reflection(rng), surface.reflectance)
+        } else {
+            let Some(dir) = surface.refracted else {
+                break;
+            };
+            specular_bounce = true;
+            (dir, transmitted)
+        };
+        throughput = throughput.mul_vec(weight) * (total / weight.luminance());
+
+        if depth + 1 >= RUSSIAN_ROULETTE_DEPTH {
+            let survival = throughput.max_component().min(0.95);
+            if rng.next_f32() >= survival {
+                break;
+            }
+            throughput = throughput * (1.0 / survival);
+        }
+        ray = Ray::with_interval(hit.point, direction, RAY_EPSILON, f32::MAX);
+        next_hit = scene.bvh.intersect(&ray, &scene.objects);
+    }
+
+    radiance
+}
+
+impl Integrator for PathTracer {
+    fn name(&self) -> &'static str {
+        "path"
+    }
+    fn shade(
+        &self,
+        ray: &Ray,
+        hit: Option<Intersection>,
+        scene: &Scene,
+        _settings: &RenderSettings,
+        rng: &mut Rng,
+    ) -> Vec3 {
+        trace(ray, hit, scene, rng)
+    }
+}
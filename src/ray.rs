@@ -5,14 +5,21 @@ pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
     pub inv_direction: Vec3,
+    pub t_min: f32,
+    pub t_max: f32,
 }
 
 impl Ray {
     pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self::with_interval(origin, direction, 0.0, f32::MAX)
+    }
+    pub fn with_interval(origin: Vec3, direction: Vec3, t_min: f32, t_max: f32) -> Self {
         Self {
             origin,
             direction,
             inv_direction: Vec3::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z),
+            t_min,
+            t_max: t_max.min(f32::MAX),
         }
     }
 }
@@ -0,0 +1,148 @@
+use crate::bvh::{Bvh, BvhConfig};
+use crate::constants::{
+    ADAPTIVE_SAMPLES, CONTRAST_THRESHOLD, MAX_BOUNCES, SAMPLES_PER_PIXEL, SHADOW_SAMPLES,
+};
+use crate::environment::Environment;
+use crate::instance::{Geometry, Instance};
+use crate::integrator::Integrator;
+use crate::light::Light;
+use crate::mat4::Mat4;
+use crate::objects::Object;
+use crate::texture::Texture;
+use crate::tonemap::ToneMapping;
+use crate::vec3::Vec3;
+use crate::whitted::Whitted;
+use std::sync::Arc;
+
+pub struct Animation {
+    pub instance: usize,
+    pub translation: Vec3,
+    pub rotation: Vec3,
+    pub scale: Vec3,
+    pub spin: Vec3,
+}
+
+pub struct Scene {
+    pub objects: Vec<Object>,
+    pub bvh: Bvh,
+    pub lights: Vec<Light>,
+    pub textures: Vec<Texture>,
+    pub environment: Environment,
+    pub animations: Vec<Animation>,
+    static_lights: usize,
+    bvh_config: BvhConfig,
+    dirty: bool,
+}
+
+impl Scene {
+    pub fn new(
+        objects: Vec<Object>,
+        lights: Vec<Light>,
+        textures: Vec<Texture>,
+        bvh_config: &BvhConfig,
+    ) -> Self {
+        let primitives: usize = objects.iter().map(Object::primitive_count).sum();
+        println!(
+            "Building BVH for {} primitives in {} objects...",
+            primitives,
+            objects.len()
+        );
+
+        let (mut instances, rest): (Vec<Object>, Vec<Object>) = objects
+            .into_iter()
+            .partition(|object| matches!(object, Object::Instance(_)));
+        let (meshes, loose): (Vec<Object>, Vec<Object>) = rest
+            .into_iter()
+            .partition(|object| matches!(object, Object::Mesh(_)));
+        let blas = meshes
+            .into_iter()
+            .map(|mesh| vec![mesh])
+            .chain((!loose.is_empty()).then_some(loose));
+        for objects in blas {
+            let geometry = Arc::new(Geometry::new(objects, bvh_config));
+            let instance = Instance::new(geometry, Mat4::identity())
+                .expect("identity transform is invertible");
+            instances.push(Object::Instance(instance));
+        }
+
+        let mut scene = Self {
+            bvh: Bvh::build(&instances, bvh_config),
+            objects: instances,
+            static_lights: lights.len(),
+            lights,
+            textures,
+            environment: Environment::constant(Vec3::new(0.05, 0.05, 0.1)),
+            animations: Vec::new(),
+            bvh_config: *bvh_config,
+            dirty: false,
+        };
+        scene.refresh_emitters();
+        scene
+    }
+
+    fn refresh_emitters(&mut self) {
+        self.lights.truncate(self.static_lights);
+        self.lights
+            .extend(self.objects.iter().flat_map(Object::emitters));
+    }
+
+    pub fn set_transform(&mut self, instance: usize, transform: Mat4) -> bool {
+        let Some(Object::Instance(target)) = self.objects.get_mut(instance) else {
+            return false;
+        };
+        let updated = target.set_transform(transform);
+        self.dirty |= updated;
+        updated
+    }
+
+    pub fn animate(&mut self, frame: u32) {
+        for i in 0..self.animations.len() {
+            let animation = &self.animations[i];
+            let transform = Mat4::from_trs(
+                animation.translation,
+                animation.rotation + animation.spin * frame as f32,
+                animation.scale,
+            );
+            let instance = animation.instance;
+            self.set_transform(instance, transform);
+        }
+    }
+
+    pub fn update(&mut self) -> bool {
+        if !self.dirty {
+            return false;
+        }
+        self.bvh.refit(&self.objects);
+        if self.bvh.needs_rebuild(&self.bvh_config) {
+            self.bvh = Bvh::build(&self.objects, &self.bvh_config);
+        }
+        self.refresh_emitters();
+        self.dirty = false;
+        true
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct RenderSettings {
+    pub max_bounces: u8,
+    pub shadow_samples: u32,
+    pub samples_per_pixel: u32,
+    pub adaptive_samples: u32,
+    pub contrast_threshold: f32,
+    pub tone_mapping: ToneMapping,
+    pub integrator: &'static dyn Integrator,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            max_bounces: MAX_BOUNCES,
+            shadow_samples: SHADOW_SAMPLES,
+            samples_per_pixel: SAMPLES_PER_PIXEL,
+            adaptive_samples: ADAPTIVE_SAMPLES,
+            contrast_threshold: CONTRAST_THRESHOLD,
+            tone_mapping: ToneMapping::default(),
+            integrator: &Whitted,
+        }
+    }
+}
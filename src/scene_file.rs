@@ -0,0 +1,860 @@
+use crate::bvh::BvhConfig;
+use crate::camera::Camera;
+use crate::environment::{Environment, EnvironmentMap};
+use crate::instance::{Geometry, Instance};
+use crate::integrator::{self, INTEGRATORS};
+use crate::light::{
+    DirectionalLight, DiscLight, HemisphereLight, Light, PointLight, RectLight, SphereLight,
+    SpotLight,
+};
+use crate::mat4::Mat4;
+use crate::material::{CONDUCTORS, Material, Shading, conductor};
+use crate::mesh::load_obj;
+use crate::objects::{Object, Sphere, Triangle};
+use crate::scene::{Animation, RenderSettings, Scene};
+use crate::texture::{FilterMode, Texture, WrapMode};
+use crate::tonemap::ToneMapper;
+use crate::vec3::Vec3;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use toml::Spanned;
+
+type Vec3Desc = [f32; 3];
+
+fn vec3(v: Vec3Desc) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDesc {
+    #[serde(default)]
+    camera: CameraDesc,
+    #[serde(default)]
+    render: RenderDesc,
+    environment: Option<Spanned<EnvironmentDesc>>,
+    #[serde(default)]
+    textures: HashMap<String, TextureDesc>,
+    #[serde(default)]
+    materials: HashMap<String, MaterialDesc>,
+    #[serde(default)]
+    spheres: Vec<SphereDesc>,
+    #[serde(default)]
+    triangles: Vec<TriangleDesc>,
+    #[serde(default)]
+    meshes: Vec<MeshDesc>,
+    #[serde(default)]
+    geometries: HashMap<String, Spanned<GeometryDesc>>,
+    #[serde(default)]
+    instances: Vec<InstanceDesc>,
+    #[serde(default)]
+    lights: Vec<LightDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CameraDesc {
+    position: Vec3Desc,
+    yaw: f32,
+    pitch: f32,
+}
+
+impl Default for CameraDesc {
+    fn default() -> Self {
+        Self {
+            position: [0.0, 2.0, 5.0],
+            yaw: -90.0,
+            pitch: -20.0,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RenderDesc {
+    max_bounces: Option<u8>,
+    shadow_samples: Option<u32>,
+    samples_per_pixel: Option<u32>,
+    adaptive_samples: Option<u32>,
+    contrast_threshold: Option<f32>,
+    tone_mapper: Option<Spanned<String>>,
+    exposure: Option<f32>,
+    auto_exposure: Option<bool>,
+    white_point: Option<f32>,
+    integrator: Option<Spanned<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentDesc {
+    color: Option<Vec3Desc>,
+    path: Option<Spanned<String>>,
+    faces: Option<[Spanned<String>; 6]>,
+    #[serde(default)]
+    rotation: Vec3Desc,
+    #[serde(default = "default_intensity")]
+    intensity: f32,
+    #[serde(default = "default_lighting")]
+    lighting: bool,
+}
+
+fn default_intensity() -> f32 {
+    1.0
+}
+
+fn default_lighting() -> bool {
+    true
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum WrapDesc {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum FilterDesc {
+    Nearest,
+    Bilinear,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TextureDesc {
+    path: Spanned<String>,
+    #[serde(default = "default_wrap")]
+    wrap: WrapDesc,
+    #[serde(default = "default_filter")]
+    filter: FilterDesc,
+}
+
+fn default_wrap() -> WrapDesc {
+    WrapDesc::Repeat
+}
+
+fn default_filter() -> FilterDesc {
+    FilterDesc::Bilinear
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct MaterialDesc {
+    albedo: Vec3Desc,
+    emission: Vec3Desc,
+    specular: f32,
+    shininess: f32,
+    checkered: bool,
+    ior: f32,
+    transmission: f32,
+    albedo_texture: Option<Spanned<String>>,
+    metallic: Option<Spanned<f32>>,
+    roughness: Option<f32>,
+    conductor: Option<Spanned<String>>,
+    eta: Option<Spanned<Vec3Desc>>,
+    k: Option<Spanned<Vec3Desc>>,
+}
+
+impl Default for MaterialDesc {
+    fn default() -> Self {
+        Self {
+            albedo: [0.8, 0.8, 0.8],
+            emission: [0.0, 0.0, 0.0],
+            specular: 0.0,
+            shininess: 32.0,
+            checkered: false,
+            ior: 1.0,
+            transmission: 0.0,
+            albedo_texture: None,
+            metallic: None,
+            roughness: None,
+            conductor: None,
+            eta: None,
+            k: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SphereDesc {
+    center: Vec3Desc,
+    radius: Spanned<f32>,
+    material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriangleDesc {
+    vertices: [Vec3Desc; 3],
+    uvs: Option<[[f32; 2]; 3]>,
+    normals: Option<[Vec3Desc; 3]>,
+    material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MeshDesc {
+    path: Spanned<String>,
+    #[serde(default)]
+    translation: Vec3Desc,
+    #[serde(default = "default_scale")]
+    scale: f32,
+    material: Spanned<String>,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GeometryDesc {
+    #[serde(default)]
+    spheres: Vec<SphereDesc>,
+    #[serde(default)]
+    triangles: Vec<TriangleDesc>,
+    #[serde(default)]
+    meshes: Vec<MeshDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InstanceDesc {
+    geometry: Spanned<String>,
+    #[serde(default)]
+    translation: Vec3Desc,
+    #[serde(default)]
+    rotation: Vec3Desc,
+    #[serde(default = "default_instance_scale")]
+    scale: Vec3Desc,
+    #[serde(default)]
+    spin: Vec3Desc,
+}
+
+fn default_instance_scale() -> Vec3Desc {
+    [1.0, 1.0, 1.0]
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum LightDesc {
+    Point {
+        position: Vec3Desc,
+        color: Vec3Desc,
+        intensity: f32,
+    },
+    Directional {
+        direction: Vec3Desc,
+        color: Vec3Desc,
+        intensity: f32,
+    },
+    Spot {
+        position: Vec3Desc,
+        direction: Vec3Desc,
+        color: Vec3Desc,
+        intensity: f32,
+        cone_angle: f32,
+        falloff: f32,
+    },
+    Hemisphere {
+        sky_color: Vec3Desc,
+        ground_color: Vec3Desc,
+        intensity: f32,
+    },
+    Sphere {
+        center: Vec3Desc,
+        radius: f32,
+        emission: Vec3Desc,
+    },
+    Rect {
+        corner: Vec3Desc,
+        edge_u: Vec3Desc,
+        edge_v: Vec3Desc,
+        emission: Vec3Desc,
+    },
+    Disc {
+        center: Vec3Desc,
+        normal: Vec3Desc,
+        radius: f32,
+        emission: Vec3Desc,
+    },
+}
+
+impl From<LightDesc> for Light {
+    fn from(desc: LightDesc) -> Self {
+        match desc {
+            LightDesc::Point {
+                position,
+                color,
+                intensity,
+            } => Light::Point(PointLight {
+                position: vec3(position),
+                color: vec3(color),
+                intensity,
+            }),
+            LightDesc::Directional {
+                direction,
+                color,
+                intensity,
+            } => Light::Directional(DirectionalLight {
+                direction: vec3(direction),
+                color: vec3(color),
+                intensity,
+            }),
+            LightDesc::Spot {
+                position,
+                direction,
+                color,
+                intensity,
+                cone_angle,
+                falloff,
+            } => Light::Spot(SpotLight {
+                position: vec3(position),
+                direction: vec3(direction),
+                color: vec3(color),
+                intensity,
+                cone_angle,
+                falloff,
+            }),
+            LightDesc::Hemisphere {
+                sky_color,
+                ground_color,
+                intensity,
+            } => Light::Hemisphere(HemisphereLight {
+                sky_color: vec3(sky_color),
+                ground_color: vec3(ground_color),
+                intensity,
+            }),
+            LightDesc::Sphere {
+                center,
+                radius,
+                emission,
+            } => Light::Sphere(SphereLight {
+                center: vec3(center),
+                radius,
+                emission: vec3(emission),
+                geometric: false,
+            }),
+            LightDesc::Rect {
+                corner,
+                edge_u,
+                edge_v,
+                emission,
+            } => Light::Rect(RectLight {
+                corner: vec3(corner),
+                edge_u: vec3(edge_u),
+                edge_v: vec3(edge_v),
+                emission: vec3(emission),
+            }),
+            LightDesc::Disc {
+                center,
+                normal,
+                radius,
+                emission,
+            } => Light::Disc(DiscLight {
+                center: vec3(center),
+                normal: vec3(normal),
+                radius,
+                emission: vec3(emission),
+            }),
+        }
+    }
+}
+
+struct Loader<'a> {
+    path: &'a Path,
+    source: &'a str,
+    texture_ids: HashMap<String, usize>,
+    materials: HashMap<String, Material>,
+}
+
+impl Loader<'_> {
+    fn error(&self, span: Option<Range<usize>>, message: &str) -> String {
+        match span {
+            Some(span) => {
+                let before = &self.source[..span.start.min(self.source.len())];
+                let line = before.matches('\n').count() + 1;
+                let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+                format!("{}:{}:{}: {}", self.path.display(), line, column, message)
+            }
+            None => format!("{}: {}", self.path.display(), message),
+        }
+    }
+
+    fn texture(&self, name: &Spanned<String>) -> Result<usize, String> {
+        self.texture_ids
+            .get(name.get_ref())
+            .copied()
+            .ok_or_else(|| {
+                self.error(
+                    Some(name.span()),
+                    &format!("unknown texture '{}'", name.get_ref()),
+                )
+            })
+    }
+
+    fn build_material(&self, desc: &MaterialDesc) -> Result<Material, String> {
+        Ok(Material {
+            albedo: vec3(desc.albedo),
+            emission: vec3(desc.emission),
+            specular: desc.specular,
+            shininess: desc.shininess,
+            checkered: desc.checkered,
+            ior: desc.ior,
+            transmission: desc.transmission,
+            albedo_texture: desc
+                .albedo_texture
+                .as_ref()
+                .map(|name| self.texture(name))
+                .transpose()?,
+            shading: self.shading(desc)?,
+        })
+    }
+
+    fn shading(&self, desc: &MaterialDesc) -> Result<Shading, String> {
+        let roughness = desc.roughness.map(|r| r.clamp(0.0, 1.0));
+        let metallic_span = desc.metallic.as_ref().map(Spanned::span);
+        let eta_span = desc.eta.as_ref().map(Spanned::span);
+        let k_span = desc.k.as_ref().map(Spanned::span);
+        if let Some(name) = &desc.conductor {
+            if let Some(span) = eta_span.or(k_span).or(metallic_span) {
+                return Err(self.error(
+                    Some(span),
+                    "'conductor' presets cannot be combined with 'eta', 'k' or 'metallic'",
+                ));
+            }
+            return conductor(name.get_ref(), roughness.unwrap_or(0.0)).ok_or_else(|| {
+                let presets: Vec<&str> = CONDUCTORS.iter().map(|(preset, _, _)| *preset).collect();
+                self.error(
+                    Some(name.span()),
+                    &format!(
+                        "unknown conductor '{}', expected one of {}",
+                        name.get_ref(),
+                        presets.join(", ")
+                    ),
+                )
+            });
+        }
+        let metallic = desc.metallic.as_ref().map(|m| *m.get_ref());
+        Ok(match (&desc.eta, &desc.k, metallic, roughness) {
+            (Some(_), Some(_), Some(_), _) => {
+                return Err(self.error(
+                    metallic_span,
+                    "conductor materials cannot also set 'metallic'",
+                ));
+            }
+            (Some(eta), Some(k), None, roughness) => Shading::Conductor {
+                eta: vec3(*eta.get_ref()),
+                k: vec3(*k.get_ref()),
+                roughness: roughness.unwrap_or(0.0),
+            },
+            (Some(_), None, _, _) | (None, Some(_), _, _) => {
+                return Err(self.error(
+                    eta_span.or(k_span),
+                    "conductor materials need both 'eta' and 'k'",
+                ));
+            }
+            (None, None, None, None) => Shading::Phong,
+            (None, None, metallic, roughness) => Shading::MetallicRoughness {
+                metallic: metallic.unwrap_or(0.0).clamp(0.0, 1.0),
+                roughness: roughness.unwrap_or(0.5),
+            },
+        })
+    }
+
+    fn material(&self, name: &Spanned<String>) -> Result<Material, String> {
+        self.materials.get(name.get_ref()).copied().ok_or_else(|| {
+            self.error(
+                Some(name.span()),
+                &format!("unknown material '{}'", name.get_ref()),
+            )
+        })
+    }
+
+    fn image(
+        &self,
+        base_dir: &Path,
+        name: &Spanned<String>,
+        wrap: WrapMode,
+    ) -> Result<Texture, String> {
+        Texture::load(&base_dir.join(name.get_ref()), wrap, FilterMode::Bilinear).map_err(|e| {
+            self.error(
+                Some(name.span()),
+                &format!("failed to load environment map: {}", e),
+            )
+        })
+    }
+
+    fn environment(
+        &self,
+        base_dir: &Path,
+        spanned: &Spanned<EnvironmentDesc>,
+    ) -> Result<Environment, String> {
+        let desc = spanned.get_ref();
+        let map = match (&desc.color, &desc.path, &desc.faces) {
+            (Some(color), None, None) => EnvironmentMap::Constant(vec3(*color)),
+            (None, Some(path), None) => {
+                EnvironmentMap::Equirectangular(self.image(base_dir, path, WrapMode::Repeat)?)
+            }
+            (None, None, Some(faces)) => {
+                let [px, nx, py, ny, pz, nz] = faces;
+                EnvironmentMap::Cube(Box::new([
+                    self.image(base_dir, px, WrapMode::Clamp)?,
+                    self.image(base_dir, nx, WrapMode::Clamp)?,
+                    self.image(base_dir, py, WrapMode::Clamp)?,
+                    self.image(base_dir, ny, WrapMode::Clamp)?,
+                    self.image(base_dir, pz, WrapMode::Clamp)?,
+                    self.image(base_dir, nz, WrapMode::Clamp)?,
+                ]))
+            }
+            _ => {
+                return Err(self.error(
+                    Some(spanned.span()),
+                    "environment needs exactly one of 'color', 'path' or 'faces'",
+                ));
+            }
+        };
+        Ok(Environment::new(
+            map,
+            vec3(desc.rotation),
+            desc.intensity,
+            desc.lighting,
+        ))
+    }
+
+    fn objects(
+        &self,
+        spheres: &[SphereDesc],
+        triangles: &[TriangleDesc],
+        meshes: &[MeshDesc],
+        textures: &mut Vec<Texture>,
+    ) -> Result<Vec<Object>, String> {
+        let base_dir = self.path.parent().unwrap_or(Path::new(""));
+        let mut objects = Vec::new();
+        for sphere in spheres {
+            if *sphere.radius.get_ref() <= 0.0 {
+                return Err(
+                    self.error(Some(sphere.radius.span()), "sphere radius must be positive")
+                );
+            }
+            objects.push(Object::Sphere(Sphere {
+                center: vec3(sphere.center),
+                radius: *sphere.radius.get_ref(),
+                mat: self.material(&sphere.material)?,
+            }));
+        }
+        for triangle in triangles {
+            objects.push(Object::Triangle(Triangle {
+                v0: vec3(triangle.vertices[0]),
+                v1: vec3(triangle.vertices[1]),
+                v2: vec3(triangle.vertices[2]),
+                uvs: triangle.uvs.map(|uvs| uvs.map(|[u, v]| (u, v))),
+                normals: triangle.normals.map(|normals| normals.map(vec3)),
+                mat: self.material(&triangle.material)?,
+            }));
+        }
+        for mesh in meshes {
+            let mat = self.material(&mesh.material)?;
+            let mesh_path = base_dir.join(mesh.path.get_ref());
+            let loaded = load_obj(
+                &mesh_path.to_string_lossy(),
+                vec3(mesh.translation),
+                mesh.scale,
+                mat,
+                textures,
+            )
+            .map_err(|e| {
+                self.error(
+                    Some(mesh.path.span()),
+                    &format!("failed to load '{}': {}", mesh_path.display(), e),
+                )
+            })?;
+            objects.push(Object::Mesh(loaded));
+        }
+        Ok(objects)
+    }
+}
+
+pub fn load_scene(
+    path: &Path,
+    bvh_config: &BvhConfig,
+) -> Result<(Scene, Camera, RenderSettings), String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut loader = Loader {
+        path,
+        source: &source,
+        texture_ids: HashMap::new(),
+        materials: HashMap::new(),
+    };
+    let desc: SceneDesc =
+        toml::from_str(&source).map_err(|e| loader.error(e.span(), e.message()))?;
+
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut textures = Vec::new();
+    for (name, texture) in &desc.textures {
+        let wrap = match texture.wrap {
+            WrapDesc::Repeat => WrapMode::Repeat,
+            WrapDesc::Clamp => WrapMode::Clamp,
+            WrapDesc::Mirror => WrapMode::Mirror,
+        };
+        let filter = match texture.filter {
+            FilterDesc::Nearest => FilterMode::Nearest,
+            FilterDesc::Bilinear => FilterMode::Bilinear,
+        };
+        let texture_path = base_dir.join(texture.path.get_ref());
+        let loaded = Texture::load(&texture_path, wrap, filter).map_err(|e| {
+            loader.error(
+                Some(texture.path.span()),
+                &format!("failed to load texture: {}", e),
+            )
+        })?;
+        loader.texture_ids.insert(name.clone(), textures.len());
+        textures.push(loaded);
+    }
+
+    for (name, material) in &desc.materials {
+        let material = loader.build_material(material)?;
+        loader.materials.insert(name.clone(), material);
+    }
+
+    let mut objects =
+        loader.objects(&desc.spheres, &desc.triangles, &desc.meshes, &mut textures)?;
+
+    let mut geometries = HashMap::new();
+    for (name, spanned) in &desc.geometries {
+        let geometry = spanned.get_ref();
+        let geometry_objects = loader.objects(
+            &geometry.spheres,
+            &geometry.triangles,
+            &geometry.meshes,
+            &mut textures,
+        )?;
+        if geometry_objects.is_empty() {
+            return Err(loader.error(
+                Some(spanned.span()),
+                &format!("geometry '{}' is empty", name),
+            ));
+        }
+        geometries.insert(
+            name.clone(),
+            Arc::new(Geometry::new(geometry_objects, bvh_config)),
+        );
+    }
+    let mut animations = Vec::new();
+    for (index, instance) in desc.instances.iter().enumerate() {
+        let geometry = geometries.get(instance.geometry.get_ref()).ok_or_else(|| {
+            loader.error(
+                Some(instance.geometry.span()),
+                &format!("unknown geometry '{}'", instance.geometry.get_ref()),
+            )
+        })?;
+        let [sx, sy, sz] = instance.scale.map(f32::abs);
+        if geometry.has_sphere_lights() && (sx != sy || sy != sz) {
+            return Err(loader.error(
+                Some(instance.geometry.span()),
+                "instances of geometry with emissive spheres need a uniform scale",
+            ));
+        }
+        let transform = Mat4::from_trs(
+            vec3(instance.translation),
+            vec3(instance.rotation),
+            vec3(instance.scale),
+        );
+        if instance.spin != [0.0; 3] {
+            animations.push(Animation {
+                instance: index,
+                translation: vec3(instance.translation),
+                rotation: vec3(instance.rotation),
+                scale: vec3(instance.scale),
+                spin: vec3(instance.spin),
+            });
+        }
+        let instance = Instance::new(geometry.clone(), transform).ok_or_else(|| {
+            loader.error(
+                Some(instance.geometry.span()),
+                "instance transform is not invertible",
+            )
+        })?;
+        objects.push(Object::Instance(instance));
+    }
+    if objects.is_empty() {
+        return Err(loader.error(
+            None,
+            "scene needs at least one sphere, triangle, mesh or instance",
+        ));
+    }
+
+    let lights = desc.lights.into_iter().map(Light::from).collect();
+
+    let mut camera = Camera::new(vec3(desc.camera.position), (0.0, 0.0));
+    camera.yaw = desc.camera.yaw;
+    camera.pitch = desc.camera.pitch;
+
+    let mut settings = RenderSettings::default();
+    if let Some(max_bounces) = desc.render.max_bounces {
+        settings.max_bounces = max_bounces;
+    }
+    if let Some(shadow_samples) = desc.render.shadow_samples {
+        settings.shadow_samples = shadow_samples;
+    }
+    if let Some(samples_per_pixel) = desc.render.samples_per_pixel {
+        settings.samples_per_pixel = samples_per_pixel;
+    }
+    if let Some(adaptive_samples) = desc.render.adaptive_samples {
+        settings.adaptive_samples = adaptive_samples;
+    }
+    if let Some(contrast_threshold) = desc.render.contrast_threshold {
+        settings.contrast_threshold = contrast_threshold;
+    }
+    if let Some(name) = &desc.render.tone_mapper {
+        settings.tone_mapping.operator = ToneMapper::parse(name.get_ref()).ok_or_else(|| {
+            loader.error(
+                Some(name.span()),
+                &format!("unknown tone mapper '{}'", name.get_ref()),
+            )
+        })?;
+    }
+    if let Some(exposure) = desc.render.exposure {
+        settings.tone_mapping.exposure = exposure;
+    }
+    if let Some(auto_exposure) = desc.render.auto_exposure {
+        settings.tone_mapping.auto_exposure = auto_exposure;
+    }
+    if let Some(white_point) = desc.render.white_point {
+        settings.tone_mapping.white_point = white_point;
+    }
+    if let Some(name) = &desc.render.integrator {
+        settings.integrator = integrator::find(name.get_ref()).ok_or_else(|| {
+            let names: Vec<&str> = INTEGRATORS.iter().map(|i| i.name()).collect();
+            loader.error(
+                Some(name.span()),
+                &format!(
+                    "unknown integrator '{}', expected one of: {}",
+                    name.get_ref(),
+                    names.join(", ")
+                ),
+            )
+        })?;
+    }
+
+    let mut scene = Scene::new(objects, lights, textures, bvh_config);
+    scene.animations = animations;
+    if let Some(environment) = &desc.environment {
+        scene.environment = loader.environment(base_dir, environment)?;
+    }
+    Ok((scene, camera, settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_error(name: &str, source: &str) -> (String, String) {
+        let path =
+            std::env::temp_dir().join(format!("raytracer-{}-{}.toml", std::process::id(), name));
+        fs::write(&path, source).unwrap();
+        let result = load_scene(&path, &BvhConfig::default());
+        fs::remove_file(&path).unwrap();
+        let Err(error) = result else {
+            panic!("{} loaded without an error", name);
+        };
+        (path.display().to_string(), error)
+    }
+
+    #[test]
+    fn syntax_errors_report_line_and_column() {
+        let (path, error) = load_error("syntax", "[camera]\nposition = [0.0, 1.0\n");
+        assert!(error.starts_with(&format!("{}:2:", path)), "{}", error);
+    }
+
+    #[test]
+    fn unknown_keys_report_line_and_column() {
+        let (path, error) = load_error(
+            "unknown-key",
+            "[camera]\nposition = [0.0, 1.0, 2.0]\n\n[materials.red]\nalbedo = [1.0, 0.0, 0.0]\nglossy = 1.0\n",
+        );
+        assert!(error.starts_with(&format!("{}:6:1:", path)), "{}", error);
+        assert!(error.contains("glossy"), "{}", error);
+    }
+
+    #[test]
+    fn unknown_material_points_at_the_reference() {
+        let (path, error) = load_error(
+            "unknown-material",
+            "[camera]\nposition = [0.0, 1.0, 2.0]\n\n[[spheres]]\ncenter = [0.0, 0.0, 0.0]\nradius = 1.0\nmaterial = \"missing\"\n",
+        );
+        assert_eq!(error, format!("{}:7:12: unknown material 'missing'", path));
+    }
+
+    #[test]
+    fn conductor_preset_rejects_explicit_optics() {
+        let (path, error) = load_error(
+            "conductor-conflict",
+            "[camera]\nposition = [0.0, 1.0, 2.0]\n\n[materials.gold]\nconductor = \"gold\"\nk = [3.0, 2.0, 1.8]\n",
+        );
+        assert!(error.starts_with(&format!("{}:6:5:", path)), "{}", error);
+    }
+
+    #[test]
+    fn conductor_needs_eta_and_k() {
+        let (path, error) = load_error(
+            "conductor-eta",
+            "[camera]\nposition = [0.0, 1.0, 2.0]\n\n[materials.metal]\neta = [0.2, 0.4, 1.4]\n",
+        );
+        assert_eq!(
+            error,
+            format!("{}:5:7: conductor materials need both 'eta' and 'k'", path)
+        );
+    }
+
+    #[test]
+    fn scenes_without_geometry_are_rejected() {
+        let (path, error) = load_error(
+            "empty",
+            "[camera]\nposition = [0.0, 1.0, 2.0]\n\n[[lights]]\ntype = \"point\"\nposition = [0.0, 5.0, 0.0]\ncolor = [1.0, 1.0, 1.0]\nintensity = 10.0\n",
+        );
+        assert_eq!(
+            error,
+            format!(
+                "{}: scene needs at least one sphere, triangle, mesh or instance",
+                path
+            )
+        );
+    }
+
+    #[test]
+    fn environment_errors_point_at_the_table() {
+        let (path, error) = load_error(
+            "environment",
+            "[camera]\nposition = [0.0, 1.0, 2.0]\n\n[materials.red]\n\n[[spheres]]\ncenter = [0.0, 0.0, 0.0]\nradius = 1.0\nmaterial = \"red\"\n\n[environment]\nintensity = 2.0\n",
+        );
+        assert!(error.starts_with(&format!("{}:11:1:", path)), "{}", error);
+    }
+
+    #[test]
+    fn empty_geometry_points_at_the_table() {
+        let (path, error) = load_error(
+            "empty-geometry",
+            "[camera]\nposition = [0.0, 1.0, 2.0]\n\n[geometries.nothing]\nspheres = []\n",
+        );
+        assert!(error.starts_with(&format!("{}:4:1:", path)), "{}", error);
+        assert!(error.ends_with("geometry 'nothing' is empty"), "{}", error);
+    }
+
+    #[test]
+    fn invalid_radius_points_at_the_value() {
+        let (path, error) = load_error(
+            "radius",
+            "[camera]\nposition = [0.0, 1.0, 2.0]\n\n[materials.red]\n\n[[spheres]]\ncenter = [0.0, 0.0, 0.0]\nradius = -1.0\nmaterial = \"red\"\n",
+        );
+        assert!(error.starts_with(&format!("{}:8:10:", path)), "{}", error);
+    }
+}
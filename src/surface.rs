@@ -0,0 +1,176 @@
+use crate::brdf;
+use crate::material::Shading;
+use crate::objects::Intersection;
+use crate::ray::Ray;
+use crate::rng::Rng;
+use crate::texture::Texture;
+use crate::vec3::Vec3;
+use std::f32::consts::PI;
+
+pub struct Surface {
+    pub normal: Vec3,
+    pub view_dir: Vec3,
+    pub albedo: Vec3,
+    pub fresnel: f32,
+    pub refracted: Option<Vec3>,
+    pub diffuse: Vec3,
+    pub f0: Vec3,
+    pub reflectance: Vec3,
+    pub ambient_diffuse: Vec3,
+    shading: Shading,
+    shininess: f32,
+}
+
+fn schlick_fresnel(f0: f32, cos_theta: f32) -> f32 {
+    f0 + (1.0 - f0) * (1.0 - cos_theta).powi(5)
+}
+
+impl Surface {
+    pub fn new(ray: &Ray, hit: &Intersection, textures: &[Texture]) -> Self {
+        let material = &hit.material;
+        let mut albedo = material.albedo;
+        if let Some(texture) = material.albedo_texture {
+            albedo = albedo.mul_vec(textures[texture].sample(hit.uv.0, hit.uv.1));
+        }
+        if material.checkered {
+            let size = 1.0;
+            let x = (hit.point.x * size).floor() as i32;
+            let z = (hit.point.z * size).floor() as i32;
+            if (x + z) % 2 != 0 {
+                albedo = Vec3::new(0.1, 0.1, 0.1);
+            }
+        }
+
+        let view_dir = -ray.direction;
+        let n_dot_v = hit.normal.dot(view_dir).max(0.0);
+        let transmission = material.transmission;
+
+        let mut refracted = None;
+        let fresnel = if transmission > 0.0 {
+            let eta = if hit.front_face {
+                1.0 / material.ior
+            } else {
+                material.ior
+            };
+            let f0 = ((1.0 - material.ior) / (1.0 + material.ior)).powi(2);
+            match ray.direction.refract(hit.normal, eta) {
+                Some(dir) => {
+                    let cos_theta = if eta > 1.0 {
+                        (-dir).dot(hit.normal).max(0.0)
+                    } else {
+                        n_dot_v
+                    };
+                    refracted = Some(dir.normalize());
+                    schlick_fresnel(f0, cos_theta)
+                }
+                None => 1.0,
+            }
+        } else {
+            schlick_fresnel(material.specular, n_dot_v)
+        };
+
+        let k_diffuse = (1.0 - fresnel) * (1.0 - transmission);
+
+        let (diffuse, f0, reflectance) = match material.shading {
+            Shading::Phong => (
+                albedo * k_diffuse,
+                Vec3::new(fresnel, fresnel, fresnel),
+                Vec3::new(fresnel, fresnel, fresnel),
+            ),
+            Shading::MetallicRoughness { metallic, .. } => {
+                let f0 = brdf::base_reflectance(albedo, metallic);
+                let reflectance = if transmission > 0.0 {
+                    Vec3::new(fresnel, fresnel, fresnel)
+                } else {
+                    brdf::fresnel_schlick(f0, n_dot_v)
+                };
+                (
+                    albedo * ((1.0 - metallic) * (1.0 - transmission)),
+                    f0,
+                    reflectance,
+                )
+            }
+            Shading::Conductor { eta, k, .. } => (
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, 0.0),
+                brdf::conductor_fresnel(n_dot_v, eta, k),
+            ),
+        };
+        let ambient_diffuse = match material.shading {
+            Shading::Phong => diffuse,
+            _ => diffuse * (1.0 - reflectance.luminance()),
+        };
+
+        Self {
+            normal: hit.normal,
+            view_dir,
+            albedo,
+            fresnel,
+            refracted,
+            diffuse,
+            f0,
+            reflectance,
+            ambient_diffuse,
+            shading: material.shading,
+            shininess: material.shininess,
+        }
+    }
+
+    pub fn roughness(&self) -> f32 {
+        match self.shading {
+            Shading::MetallicRoughness { roughness, .. } | Shading::Conductor { roughness, .. } => {
+                roughness
+            }
+            Shading::Phong => 0.0,
+        }
+    }
+
+    fn lobes(&self, light_dir: Vec3) -> (Vec3, Vec3) {
+        match self.shading {
+            Shading::Phong => {
+                let n_dot_l = self.normal.dot(light_dir);
+                let halfway_dir = (light_dir + self.view_dir).normalize();
+                let n_dot_h = self.normal.dot(halfway_dir).max(0.0);
+                let specular_intensity = n_dot_h.powf(self.shininess) * self.fresnel;
+                (
+                    self.diffuse * (n_dot_l / PI),
+                    Vec3::new(1.0, 1.0, 1.0) * (specular_intensity / PI),
+                )
+            }
+            Shading::MetallicRoughness { roughness, .. } => brdf::cook_torrance(
+                self.normal,
+                self.view_dir,
+                light_dir,
+                |cos| brdf::fresnel_schlick(self.f0, cos),
+                self.diffuse,
+                roughness,
+            ),
+            Shading::Conductor { eta, k, roughness } => brdf::cook_torrance(
+                self.normal,
+                self.view_dir,
+                light_dir,
+                |cos| brdf::conductor_fresnel(cos, eta, k),
+                self.diffuse,
+                roughness,
+            ),
+        }
+    }
+
+    pub fn response(&self, light_dir: Vec3) -> Vec3 {
+        let (diffuse, specular) = self.lobes(light_dir);
+        diffuse + specular
+    }
+
+    pub fn diffuse_response(&self, light_dir: Vec3) -> Vec3 {
+        self.lobes(light_dir).0
+    }
+
+    pub fn reflection(&self, rng: &mut Rng) -> Vec3 {
+        let roughness = self.roughness();
+        if roughness > 0.0 {
+            brdf::sample_ggx_reflection(self.normal, self.view_dir, roughness, rng)
+        } else {
+            (-self.view_dir).reflect(self.normal).normalize()
+        }
+    }
+}
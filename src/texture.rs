@@ -0,0 +1,85 @@
+use crate::image_io::read_image;
+use crate::vec3::Vec3;
+use std::io;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterMode {
+    Nearest,
+    Bilinear,
+}
+
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<Vec3>,
+    pub wrap: WrapMode,
+    pub filter: FilterMode,
+}
+
+impl Texture {
+    pub fn load(path: &Path, wrap: WrapMode, filter: FilterMode) -> io::Result<Self> {
+        let (width, height, data) = read_image(path)?;
+        if width == 0 || height == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: empty image", path.display()),
+            ));
+        }
+        Ok(Self {
+            width,
+            height,
+            data,
+            wrap,
+            filter,
+        })
+    }
+
+    fn wrap_coord(&self, i: i64, size: usize) -> usize {
+        let size = size as i64;
+        let wrapped = match self.wrap {
+            WrapMode::Repeat => i.rem_euclid(size),
+            WrapMode::Clamp => i.clamp(0, size - 1),
+            WrapMode::Mirror => {
+                let period = i.rem_euclid(2 * size);
+                if period < size {
+                    period
+                } else {
+                    2 * size - 1 - period
+                }
+            }
+        };
+        wrapped as usize
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Vec3 {
+        let x = self.wrap_coord(x, self.width);
+        let y = self.wrap_coord(y, self.height);
+        self.data[y * self.width + x]
+    }
+
+    pub fn sample(&self, u: f32, v: f32) -> Vec3 {
+        let x = u * self.width as f32;
+        let y = (1.0 - v) * self.height as f32;
+        match self.filter {
+            FilterMode::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            FilterMode::Bilinear => {
+                let x = x - 0.5;
+                let y = y - 0.5;
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let top = self.texel(x0, y0) * (1.0 - fx) + self.texel(x0 + 1, y0) * fx;
+                let bottom = self.texel(x0, y0 + 1) * (1.0 - fx) + self.texel(x0 + 1, y0 + 1) * fx;
+                top * (1.0 - fy) + bottom * fy
+            }
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use crate::vec3::Vec3;
+use rayon::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapper {
+    Clamp,
+    Reinhard,
+    ReinhardExtended,
+    Aces,
+    Uncharted2,
+}
+
+impl ToneMapper {
+    pub const ALL: [ToneMapper; 5] = [
+        ToneMapper::Clamp,
+        ToneMapper::Reinhard,
+        ToneMapper::ReinhardExtended,
+        ToneMapper::Aces,
+        ToneMapper::Uncharted2,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "clamp" => Some(ToneMapper::Clamp),
+            "reinhard" => Some(ToneMapper::Reinhard),
+            "reinhard-extended" => Some(ToneMapper::ReinhardExtended),
+            "aces" => Some(ToneMapper::Aces),
+            "uncharted2" => Some(ToneMapper::Uncharted2),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|&m| m == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ToneMapping {
+    pub operator: ToneMapper,
+    pub exposure: f32,
+    pub auto_exposure: bool,
+    pub white_point: f32,
+    pub srgb: bool,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        Self {
+            operator: ToneMapper::Clamp,
+            exposure: 0.0,
+            auto_exposure: false,
+            white_point: 4.0,
+            srgb: true,
+        }
+    }
+}
+
+const AUTO_EXPOSURE_KEY: f32 = 0.18;
+const UNCHARTED2_WHITE: f32 = 11.2;
+
+fn uncharted2_curve(x: f32) -> f32 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+fn aces_curve(x: f32) -> f32 {
+    let x = x * 0.6;
+    (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    let c = c.clamp(0.0, 1.0);
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn pack(c: Vec3, transfer: fn(f32) -> f32) -> u32 {
+    let r = (transfer(c.x) * 255.0 + 0.5) as u32;
+    let g = (transfer(c.y) * 255.0 + 0.5) as u32;
+    let b = (transfer(c.z) * 255.0 + 0.5) as u32;
+    (255 << 24) | (r << 16) | (g << 8) | b
+}
+
+impl ToneMapping {
+    pub fn exposure_scale(&self, frame: &[Vec3]) -> f32 {
+        let manual = self.exposure.exp2();
+        if !self.auto_exposure || frame.is_empty() {
+            return manual;
+        }
+        let log_sum: f32 = frame
+            .par_iter()
+            .map(|c| (c.luminance().max(0.0) + 1e-4).ln())
+            .sum();
+        let log_average = (log_sum / frame.len() as f32).exp();
+        manual * AUTO_EXPOSURE_KEY / log_average
+    }
+
+    pub fn map(&self, color: Vec3, scale: f32) -> Vec3 {
+        let c = color * scale;
+        match self.operator {
+            ToneMapper::Clamp => c,
+            ToneMapper::Reinhard | ToneMapper::ReinhardExtended => {
+                let l = c.luminance();
+                if l <= 0.0 {
+                    return Vec3::new(0.0, 0.0, 0.0);
+                }
+                let mapped = if self.operator == ToneMapper::Reinhard {
+                    l / (1.0 + l)
+                } else {
+                    let white_sq = self.white_point * self.white_point;
+                    l * (1.0 + l / white_sq) / (1.0 + l)
+                };
+                c * (mapped / l)
+            }
+            ToneMapper::Aces => Vec3::new(aces_curve(c.x), aces_curve(c.y), aces_curve(c.z)),
+            ToneMapper::Uncharted2 => {
+                let white = 1.0 / uncharted2_curve(UNCHARTED2_WHITE);
+                Vec3::new(
+                    uncharted2_curve(2.0 * c.x) * white,
+                    uncharted2_curve(2.0 * c.y) * white,
+                    uncharted2_curve(2.0 * c.z) * white,
+                )
+            }
+        }
+    }
+
+    pub fn encode(&self, color: Vec3, scale: f32) -> u32 {
+        let transfer: fn(f32) -> f32 = if self.srgb {
+            linear_to_srgb
+        } else {
+            |c| c.clamp(0.0, 1.0)
+        };
+        pack(self.map(color, scale), transfer)
+    }
+}
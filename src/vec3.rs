@@ -1,4 +1,3 @@
-use crate::constants::GAMMA;
 use std::ops::{Add, Mul, Neg, Sub};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -53,11 +52,40 @@ impl Vec3 {
             self.z.max(other.z),
         )
     }
-    pub fn to_u32_gamma(self) -> u32 {
-        let r = (self.x.powf(1.0 / GAMMA).clamp(0.0, 1.0) * 255.0) as u32;
-        let g = (self.y.powf(1.0 / GAMMA).clamp(0.0, 1.0) * 255.0) as u32;
-        let b = (self.z.powf(1.0 / GAMMA).clamp(0.0, 1.0) * 255.0) as u32;
-        (255 << 24) | (r << 16) | (g << 8) | b
+    pub fn reflect(&self, normal: Vec3) -> Vec3 {
+        *self - 2.0 * self.dot(normal) * normal
+    }
+    pub fn refract(&self, normal: Vec3, eta: f32) -> Option<Vec3> {
+        let cos_i = -self.dot(normal);
+        let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+        if sin2_t > 1.0 {
+            return None;
+        }
+        let cos_t = (1.0 - sin2_t).sqrt();
+        Some(eta * *self + (eta * cos_i - cos_t) * normal)
+    }
+    pub fn orthonormal_basis(&self) -> (Vec3, Vec3) {
+        let helper = if self.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let tangent = self.cross(helper).normalize();
+        let bitangent = self.cross(tangent);
+        (tangent, bitangent)
+    }
+    pub fn axis(&self, axis: usize) -> f32 {
+        match axis {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z,
+        }
+    }
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
+    }
+    pub fn max_component(&self) -> f32 {
+        self.x.max(self.y).max(self.z)
     }
 }
 
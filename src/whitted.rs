@@ -0,0 +1,139 @@
+use crate::brdf;
+use crate::constants::RAY_EPSILON;
+use crate::environment::EnvironmentMap;
+use crate::integrator::Integrator;
+use crate::objects::Intersection;
+use crate::ray::Ray;
+use crate::rng::Rng;
+use crate::scene::{RenderSettings, Scene};
+use crate::surface::Surface;
+use crate::vec3::Vec3;
+
+pub struct Whitted;
+
+fn background(direction: Vec3, scene: &Scene, depth: u8) -> Vec3 {
+    let radiance = scene.environment.radiance(direction);
+    match scene.environment.map {
+        EnvironmentMap::Constant(_) => radiance * (0.5_f32).powi(depth as i32),
+        _ => radiance,
+    }
+}
+
+fn ambient_visibility(
+    hit: &Intersection,
+    scene: &Scene,
+    settings: &RenderSettings,
+    rng: &mut Rng,
+) -> f32 {
+    let samples = settings.shadow_samples.max(1);
+    let visible = (0..samples)
+        .filter(|_| {
+            let direction = brdf::sample_cosine_hemisphere(hit.normal, rng);
+            let ray = Ray::with_interval(hit.point, direction, RAY_EPSILON, f32::MAX);
+            !scene.bvh.occluded(&ray, &scene.objects)
+        })
+        .count();
+    visible as f32 / samples as f32
+}
+
+fn trace(ray: &Ray, scene: &Scene, settings: &RenderSettings, rng: &mut Rng, depth: u8) -> Vec3 {
+    match scene.bvh.intersect(ray, &scene.objects) {
+        Some(hit) => shade(ray, &hit, scene, settings, rng, depth),
+        None => background(ray.direction, scene, depth),
+    }
+}
+
+fn shade(
+    ray: &Ray,
+    hit: &Intersection,
+    scene: &Scene,
+    settings: &RenderSettings,
+    rng: &mut Rng,
+    depth: u8,
+) -> Vec3 {
+    let surface = Surface::new(ray, hit, &scene.textures);
+    let ambient = scene
+        .lights
+        .iter()
+        .fold(scene.environment.irradiance(hit.normal), |sum, light| {
+            sum + light.ambient(hit.normal)
+        });
+    let mut color = hit.material.emission;
+    if ambient.max_component() > 0.0 {
+        let visibility = ambient_visibility(hit, scene, settings, rng);
+        color = color + surface.ambient_diffuse.mul_vec(ambient) * visibility;
+    }
+    for light in &scene.lights {
+        let samples = if light.is_area() {
+            settings.shadow_samples.max(1)
+        } else {
+            1
+        };
+        let mut light_color = Vec3::new(0.0, 0.0, 0.0);
+
+        for _ in 0..samples {
+            let Some(sample) = light.sample(hit.point, rng) else {
+                continue;
+            };
+            if hit.normal.dot(sample.direction) <= 0.0 {
+                continue;
+            }
+
+            let shadow_ray = Ray::with_interval(
+                hit.point,
+                sample.direction,
+                RAY_EPSILON,
+                sample.distance - RAY_EPSILON,
+            );
+            if scene.bvh.occluded(&shadow_ray, &scene.objects) {
+                continue;
+            }
+
+            let response = surface.response(sample.direction);
+            light_color = light_color + response.mul_vec(sample.radiance);
+        }
+
+        color = color + light_color * (1.0 / samples as f32);
+    }
+
+    if depth + 1 >= settings.max_bounces {
+        return color;
+    }
+
+    if hit.material.specular > 0.0 || surface.reflectance.max_component() > 0.05 {
+        let reflect = surface.reflection(rng);
+        let reflect_ray = Ray::with_interval(hit.point, reflect, RAY_EPSILON, f32::MAX);
+        let reflected = trace(&reflect_ray, scene, settings, rng, depth + 1);
+        color = color + reflected.mul_vec(surface.reflectance);
+    }
+
+    if let Some(dir) = surface.refracted {
+        let weight = (1.0 - surface.fresnel) * hit.material.transmission;
+        if weight > 0.01 {
+            let refract_ray = Ray::with_interval(hit.point, dir, RAY_EPSILON, f32::MAX);
+            let transmitted = trace(&refract_ray, scene, settings, rng, depth + 1);
+            color = color + surface.albedo.mul_vec(transmitted) * weight;
+        }
+    }
+
+    color
+}
+
+impl Integrator for Whitted {
+    fn name(&self) -> &'static str {
+        "whitted"
+    }
+    fn shade(
+        &self,
+        ray: &Ray,
+        hit: Option<Intersection>,
+        scene: &Scene,
+        settings: &RenderSettings,
+        rng: &mut Rng,
+    ) -> Vec3 {
+        match hit {
+            Some(hit) => shade(ray, &hit, scene, settings, rng, 0),
+            None => background(ray.direction, scene, 0),
+        }
+    }
+}